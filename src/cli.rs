@@ -0,0 +1,414 @@
+use crate::output::OutputFormat;
+use crate::client::{PriorityFee, MAX_COMPUTE_UNIT_LIMIT};
+use crate::profile::PROFILE_KEYS;
+use clap::builder::{BoolishValueParser, PossibleValuesParser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use solana_sdk::pubkey::Pubkey;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "rhizo-cli",
+    version,
+    about = "Command-line interface to the rhizo network.",
+    after_help = "Exit codes:\n  0  success\n  1  general failure\n  2  usage error\n  3  local validation failure\n  4  RPC or ingest failure\n  5  on-chain program error"
+)]
+pub struct Cli {
+    /// Output format for command results and errors
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    #[command(flatten)]
+    pub profile: ProfileArgs,
+
+    #[command(flatten)]
+    pub transaction: TransactionArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Deploy a route configuration and backing WASM module. Both should validate locally.
+    Deploy {
+        /// Path to the compiled WASIX WASM module
+        wasm_module_path: PathBuf,
+        /// Path to the route configuration TOML file
+        route_config_path: PathBuf,
+        /// Send the transaction and upload the module even if the deployed route already matches
+        #[arg(long)]
+        force: bool,
+    },
+    /// Re-upload the modules of deployed routes that the ingest service is missing.
+    Repair {
+        /// Compiled WASM modules to match against the module CIDs of your routes
+        #[arg(required = true)]
+        wasm_module_paths: Vec<PathBuf>,
+    },
+    /// Initiate yanking a route from the network.
+    Yank {
+        /// Name of the route to yank
+        route_key: String,
+    },
+    /// Fetch a route or signed onchain bytes by name.
+    View {
+        #[command(subcommand)]
+        command: ViewCommand,
+    },
+    /// Fetch the list of the developer's hosted routes or signed onchain bytes.
+    Ls {
+        collection: Collection,
+    },
+    /// Manage signed on-chain bytes owned by the current keypair.
+    Socb {
+        #[command(subcommand)]
+        command: SocbCommand,
+    },
+    /// Validate a route configuration file.
+    ValidateConfig {
+        /// Path to the route configuration TOML file
+        route_config_path: PathBuf,
+    },
+    /// Validate a compiled WASIX WASM file. Only detects ABI compatibility with rhizo-server, not runtime errors.
+    ValidateModule {
+        /// Path to the compiled WASIX WASM module
+        wasm_module_path: PathBuf,
+    },
+    /// Execute the module's test() function locally. Useful for local testing and finding runtime errors.
+    TestModule {
+        /// Path to the compiled WASIX WASM module
+        wasm_module_path: PathBuf,
+    },
+    /// Show or change rhizo-cli profiles in ~/.config/rhizo/config.toml.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Explain a custom error code returned by the rhizo program. Lists every known code when none is given.
+    ExplainError {
+        /// Error code in decimal or hex, e.g. 3 or 0x3
+        code: Option<String>,
+    },
+    /// Create, inspect and withdraw from durable nonce accounts for --durable-nonce.
+    Nonce {
+        #[command(subcommand)]
+        command: NonceCommand,
+    },
+    /// Add the current keypair's signature to a transaction exported with --export-tx or --sign-only.
+    Sign {
+        /// Exported transaction file, or - for stdin
+        transaction_path: PathBuf,
+        /// Where to write the signed transaction [default: overwrite the input, or stdout for stdin]
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+    },
+    /// Broadcast a fully signed transaction exported with --export-tx or --sign-only.
+    Submit {
+        /// Signed transaction file, or - for stdin
+        transaction_path: PathBuf,
+    },
+    /// Check the signature an ingest upload carries in its X-Rhizo-* headers. Works offline.
+    VerifyUpload {
+        /// File with the upload's HTTP headers, one `Name: value` per line, or - for stdin
+        #[arg(long, value_name = "PATH")]
+        headers: Option<PathBuf>,
+        #[command(flatten)]
+        fields: UploadHeaderArgs,
+        /// WASM module that must match the signed module CID
+        #[arg(long, value_name = "PATH")]
+        module: Option<PathBuf>,
+        /// Reject signatures made more than this many seconds ago
+        #[arg(long, value_name = "SECONDS")]
+        max_age: Option<u64>,
+    },
+    /// List recent transactions signed by the current keypair, or with --local those recorded in
+    /// the local journal.
+    History {
+        /// Read the journal of transactions sent from this machine instead of querying the cluster
+        #[arg(long)]
+        local: bool,
+        /// Only show journal entries for this route or signed onchain bytes seed
+        #[arg(long, requires = "local")]
+        key: Option<String>,
+        /// Most entries to show, newest first
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// View the rhizo-cli version.
+    Version,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ViewCommand {
+    /// Fetch a route's config.
+    Route {
+        /// Name of the route
+        key: String,
+    },
+    /// Fetch signed onchain bytes and show their content.
+    Socb {
+        /// Name of the signed onchain bytes
+        key: String,
+        #[command(flatten)]
+        display: SocbDisplayArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NonceCommand {
+    /// Create a nonce account derived from the current keypair, with the keypair as its authority.
+    Create {
+        /// Seed the account address is derived from
+        #[arg(long, default_value = DEFAULT_NONCE_SEED)]
+        seed: String,
+        /// Lamports to fund the account with [default: the rent-exempt minimum]
+        #[arg(long)]
+        lamports: Option<u64>,
+    },
+    /// Show a nonce account's authority, stored blockhash and balance.
+    Show {
+        #[command(flatten)]
+        account: NonceAccountArgs,
+    },
+    /// Withdraw lamports from a nonce account. Withdrawing the whole balance closes it.
+    Withdraw {
+        /// Lamports to withdraw
+        lamports: u64,
+        #[command(flatten)]
+        account: NonceAccountArgs,
+        /// Recipient of the lamports [default: the current keypair]
+        #[arg(long, value_name = "PUBKEY")]
+        to: Option<Pubkey>,
+    },
+}
+
+/// Seed of the nonce account `nonce create` makes when none is given.
+pub const DEFAULT_NONCE_SEED: &str = "nonce";
+
+/// Which nonce account a `nonce` subcommand acts on.
+#[derive(Debug, Args)]
+pub struct NonceAccountArgs {
+    /// Nonce account address [default: derived from the current keypair and --seed]
+    #[arg(long, value_name = "PUBKEY")]
+    pub address: Option<Pubkey>,
+    /// Seed the default address is derived from
+    #[arg(long, default_value = DEFAULT_NONCE_SEED, conflicts_with = "address")]
+    pub seed: String,
+}
+
+/// How `view socb` shows the content.
+#[derive(Debug, Args)]
+pub struct SocbDisplayArgs {
+    /// Encoding of the content
+    #[arg(long, value_enum, default_value_t = ContentFormat::Hexdump)]
+    pub format: ContentFormat,
+    /// Also write the raw content to this file
+    #[arg(long, value_name = "PATH")]
+    pub out: Option<PathBuf>,
+    /// Show all of the content instead of the first 256 bytes
+    #[arg(long)]
+    pub full: bool,
+    /// Decode the content as JSON with this Borsh layout schema instead of showing bytes
+    #[arg(long, value_name = "PATH", conflicts_with = "format")]
+    pub schema: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ContentFormat {
+    /// Lowercase hex on one line
+    Hex,
+    /// Offsets, hex and printable characters, 16 bytes per line
+    Hexdump,
+    /// Standard base64
+    Base64,
+    /// UTF-8 text, with invalid sequences replaced
+    Utf8,
+    /// The bytes as they are, always in full. With --output json, requires --out
+    Raw,
+}
+
+/// Where `socb write` reads the content from. Exactly one is required.
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct SocbContentArgs {
+    /// File with the content to write, or - for stdin
+    pub content_path: Option<PathBuf>,
+    /// Content given as hex
+    #[arg(long, value_name = "HEX")]
+    pub hex: Option<String>,
+    /// Content given as standard base64
+    #[arg(long, value_name = "BASE64")]
+    pub base64: Option<String>,
+    /// JSON value to encode as Borsh with --schema, from a file or - for stdin
+    #[arg(long, value_name = "PATH", requires = "schema")]
+    pub json: Option<PathBuf>,
+}
+
+/// Upload header values given directly instead of, or overriding, a `--headers` file.
+#[derive(Debug, Args)]
+pub struct UploadHeaderArgs {
+    /// Signer's public key (X-Rhizo-Pubkey)
+    #[arg(long, value_name = "PUBKEY")]
+    pub pubkey: Option<String>,
+    /// Base58 ed25519 signature (X-Rhizo-Signature)
+    #[arg(long)]
+    pub signature: Option<String>,
+    /// Route seed, including the route- prefix (X-Rhizo-Route)
+    #[arg(long)]
+    pub route: Option<String>,
+    /// Module CID in hex (X-Rhizo-Module-Cid)
+    #[arg(long, value_name = "CID")]
+    pub module_cid: Option<String>,
+    /// Unix time of signing in seconds (X-Rhizo-Timestamp)
+    #[arg(long)]
+    pub timestamp: Option<String>,
+    /// Hex nonce (X-Rhizo-Nonce)
+    #[arg(long)]
+    pub nonce: Option<String>,
+}
+
+/// Network settings that override the active profile.
+#[derive(Debug, Args)]
+pub struct ProfileArgs {
+    /// Path of the rhizo config file [default: ~/.config/rhizo/config.toml]
+    #[arg(long, global = true, env = "RHIZO_CONFIG", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Profile to use: localnet, devnet, mainnet-beta or one defined in the config file
+    #[arg(short, long, global = true, env = "RHIZO_PROFILE")]
+    pub profile: Option<String>,
+    /// RPC URL, overriding the profile
+    #[arg(short = 'u', long, global = true, env = "RHIZO_URL")]
+    pub url: Option<String>,
+    /// Keypair file, overriding the profile. With --export-tx alone, a pubkey is accepted instead
+    #[arg(short, long, global = true, env = "RHIZO_KEYPAIR", value_name = "PATH")]
+    pub keypair: Option<String>,
+    /// Address of the rhizo program, overriding the profile
+    #[arg(long, global = true, env = "RHIZO_PROGRAM_ID", value_name = "PUBKEY")]
+    pub program_id: Option<String>,
+    /// Attempts made for each RPC or ingest call before giving up, including the first [default: 4]
+    #[arg(long, global = true, env = "RHIZO_ATTEMPTS", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub attempts: Option<u32>,
+    /// Timeout of a single RPC or ingest request, in seconds [default: 30]
+    #[arg(long, global = true, env = "RHIZO_TIMEOUT", value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+    /// PEM bundle of extra CA certificates trusted for HTTPS RPC and ingest endpoints, overriding the profile
+    #[arg(long, global = true, env = "RHIZO_CA_FILE", value_name = "PATH")]
+    pub ca_file: Option<String>,
+}
+
+/// Approval settings for commands that send transactions.
+#[derive(Debug, Args)]
+pub struct TransactionArgs {
+    /// Approve transactions without prompting
+    #[arg(short, long, global = true, env = "RHIZO_YES", value_parser = BoolishValueParser::new())]
+    pub yes: bool,
+    /// Abort without prompting when the estimated transaction fee is above this many lamports
+    #[arg(long, global = true, env = "RHIZO_MAX_FEE_LAMPORTS", value_name = "LAMPORTS")]
+    pub max_fee_lamports: Option<u64>,
+    /// Abort without prompting when the estimated rent is above this many lamports
+    #[arg(long, global = true, env = "RHIZO_MAX_RENT_LAMPORTS", value_name = "LAMPORTS")]
+    pub max_rent_lamports: Option<u64>,
+    /// Print the transaction and simulate it instead of signing and sending it
+    #[arg(long, global = true, env = "RHIZO_DRY_RUN", value_parser = BoolishValueParser::new())]
+    pub dry_run: bool,
+    /// Compute unit price in micro-lamports, or "auto" to match recent fees paid for the same accounts
+    #[arg(long, global = true, env = "RHIZO_PRIORITY_FEE", value_name = "MICRO_LAMPORTS|auto")]
+    pub priority_fee: Option<PriorityFee>,
+    /// Compute units to request for each transaction
+    #[arg(long, global = true, env = "RHIZO_COMPUTE_UNIT_LIMIT", value_name = "UNITS", value_parser = clap::value_parser!(u32).range(1..=MAX_COMPUTE_UNIT_LIMIT as i64))]
+    pub compute_unit_limit: Option<u32>,
+    /// Write transactions unsigned to this file, or - for stdout, instead of sending them, for `sign` and `submit`
+    #[arg(long, global = true, env = "RHIZO_EXPORT_TX", value_name = "PATH", conflicts_with = "dry_run")]
+    pub export_tx: Option<PathBuf>,
+    /// Export transactions signed with the current keypair; to stdout unless --export-tx is given
+    #[arg(long, global = true, env = "RHIZO_SIGN_ONLY", value_parser = BoolishValueParser::new(), conflicts_with = "dry_run")]
+    pub sign_only: bool,
+    /// Durable nonce account to use instead of a recent blockhash, so transactions do not expire until it advances
+    #[arg(long, global = true, env = "RHIZO_DURABLE_NONCE", value_name = "PUBKEY")]
+    pub durable_nonce: Option<Pubkey>,
+    /// Keypair file of the nonce authority, or its pubkey with --export-tx [default: the current keypair]
+    #[arg(long, global = true, env = "RHIZO_NONCE_AUTHORITY", value_name = "PATH")]
+    pub nonce_authority: Option<String>,
+    /// Keypair file that pays transaction fees and rent instead of the current keypair, or its pubkey with --export-tx
+    #[arg(long, global = true, env = "RHIZO_FEE_PAYER", value_name = "PATH")]
+    pub fee_payer: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Show the settings of the active profile after applying flags and environment variables.
+    Show,
+    /// Set a key in the active profile, or the one named with --profile.
+    Set {
+        #[arg(value_parser = PossibleValuesParser::new(PROFILE_KEYS))]
+        key: String,
+        /// New value. `ingest_urls` takes a comma separated list.
+        value: String,
+    },
+    /// Make a profile the active one.
+    Use {
+        profile: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SocbCommand {
+    /// Allocate signed on-chain bytes owned by the current keypair.
+    Alloc {
+        /// Name of the signed on-chain bytes
+        key: String,
+        /// Number of bytes to allocate
+        num_bytes: usize,
+    },
+    /// Grow or shrink signed on-chain bytes owned by the current keypair, keeping their content.
+    ///
+    /// The account does not record how much of it was written, so the content is assumed to end
+    /// at its last non-zero byte and only shrinking past that needs --force. Trailing zero bytes
+    /// that are part of the content are truncated without it; check with `view socb <KEY> --full`
+    /// before shrinking such content.
+    Resize {
+        /// Name of the signed on-chain bytes
+        key: String,
+        /// New size in bytes
+        num_bytes: usize,
+        /// Shrink even if that truncates content before the last non-zero byte
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write the content of signed on-chain bytes owned by the current keypair to stdout as is.
+    Cat {
+        /// Name of the signed on-chain bytes
+        key: String,
+    },
+    /// Write signed on-chain bytes owned by the current keypair, replacing their content.
+    ///
+    /// The content is sent in a single transaction, so it must fit in one: roughly 900 bytes, less
+    /// with a long name or extra options. The rhizo program cannot write at an offset, so larger
+    /// content cannot be split across transactions.
+    Write {
+        /// Name of the signed on-chain bytes
+        key: String,
+        #[command(flatten)]
+        content: SocbContentArgs,
+        /// Schema describing the Borsh layout to encode --json with
+        #[arg(long, value_name = "PATH", requires = "json")]
+        schema: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Collection {
+    Route,
+    Socb,
+}
+
+impl Collection {
+    /// Seed prefix the rhizo program uses for accounts of this collection.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Collection::Route => "route",
+            Collection::Socb => "socb",
+        }
+    }
+}
@@ -1,74 +1,462 @@
-use b64::ToBase64;
-use borsh::BorshSerialize;
-use crate::{utils, error::RhizoCLIError as Error};
-use solana_client::rpc_client::RpcClient;
-use solana_client::client_error::ClientError;
-use solana_sdk::commitment_config::CommitmentConfig;
+use borsh::{BorshDeserialize, BorshSerialize};
+use crate::{instruction, output, program_error, tls, error::RhizoCLIError as Error};
+use crate::offline::ExportedTransaction;
+use crate::profile::Profile;
+use crate::output::Progress;
+use crate::retry::{self, RetryPolicy};
+use serde::Serialize;
+use solana_client::client_error::{ClientError, Result as ClientResult};
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::account::Account;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
 use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::message::Message;
-use solana_sdk::signature::Signer;
-use solana_sdk::signer::keypair::Keypair;
-use solana_sdk::transaction::Transaction;
-use solana_sdk::transaction::TransactionError;
-use solana_sdk::instruction::InstructionError;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::system_instruction::MAX_PERMITTED_DATA_LENGTH;
+use solana_sdk::nonce::state::{Data as NonceData, State as NonceState};
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
 use solana_sdk::pubkey::Pubkey;
-use solana_program::system_program;
+use solana_program::{system_instruction, system_program};
+use solana_client::nonce_utils;
 use solana_program::pubkey::Pubkey as ProgramPubkey;
-use reqwest::header::CONTENT_TYPE;
-use serde_json::{Map, Value};
+use std::io::IsTerminal;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::str::FromStr;
 
-pub fn establish_connection() -> Result<RpcClient, Error> {
-    let rpc_url = utils::get_rpc_url()?;
-    Ok(RpcClient::new_with_commitment(
-        rpc_url,
-        CommitmentConfig::confirmed(),
-    ))
+/// Compute units the runtime grants each instruction when no limit is requested.
+const DEFAULT_UNITS_PER_INSTRUCTION: u32 = 200_000;
+/// Most compute units a transaction can request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Most content a signed on-chain bytes account can hold: the runtime's account size limit less
+/// the content's length prefix and the owner key.
+pub const MAX_SOCB_SIZE: usize = MAX_PERMITTED_DATA_LENGTH as usize - 4 - 32;
+
+/// How transactions are approved before they are signed and sent.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionOptions {
+    /// Approve without prompting.
+    pub yes: bool,
+    /// Abort when the estimated fee is above this many lamports.
+    pub max_fee_lamports: Option<u64>,
+    /// Abort when the estimated rent is above this many lamports.
+    pub max_rent_lamports: Option<u64>,
+    /// Build and simulate the transaction without signing or sending it.
+    pub dry_run: bool,
+    /// Compute unit price to bid, if any.
+    pub priority_fee: Option<PriorityFee>,
+    /// Compute units to request instead of the runtime default.
+    pub compute_unit_limit: Option<u32>,
+    /// Write the transaction to this file, or stdout for `-`, instead of sending it.
+    pub export_tx: Option<PathBuf>,
+    /// Sign exported transactions with the local keypair. Without it they are exported unsigned.
+    pub sign_only: bool,
+    /// Durable nonce account whose stored blockhash replaces a recent one, so the transaction
+    /// does not expire until the nonce is advanced.
+    pub nonce: Option<Pubkey>,
+    /// Keypair file or pubkey of the nonce authority, when it is not the caller.
+    pub nonce_authority: Option<String>,
+    /// Keypair file or pubkey of the account that pays the fee and rent, when it is not the caller.
+    pub fee_payer: Option<String>,
+}
+
+/// Keypairs that sign a transaction built by the functions below.
+pub struct Signers {
+    /// Owner of the accounts the transaction changes. Also pays the fee unless `fee_payer` is set.
+    pub caller: Box<dyn Signer>,
+    /// Authority of the `--durable-nonce` account, when it is not the caller.
+    pub nonce_authority: Option<Box<dyn Signer>>,
+    /// Pays the fee and rent instead of the caller, given with `--fee-payer`.
+    pub fee_payer: Option<Box<dyn Signer>>,
+}
+
+impl Signers {
+    pub fn fee_payer(&self) -> &dyn Signer {
+        self.fee_payer.as_deref().unwrap_or(self.caller.as_ref())
+    }
+
+    pub fn nonce_authority(&self) -> &dyn Signer {
+        self.nonce_authority.as_deref().unwrap_or(self.caller.as_ref())
+    }
+
+    /// The signers `message` requires, each once and the fee payer first.
+    pub fn for_message(&self, message: &Message) -> Vec<&dyn Signer> {
+        let required = message.signer_keys();
+        let mut signers: Vec<&dyn Signer> = vec![];
+        for signer in [Some(self.fee_payer()), Some(self.caller.as_ref()), self.nonce_authority.as_deref()].into_iter().flatten() {
+            let pubkey = signer.pubkey();
+            if required.contains(&&pubkey) && signers.iter().all(|added| added.pubkey() != pubkey) {
+                signers.push(signer);
+            }
+        }
+        signers
+    }
+}
+
+/// Compute unit price given with `--priority-fee`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriorityFee {
+    /// A fixed price in micro-lamports per compute unit.
+    MicroLamports(u64),
+    /// The 75th percentile of the prioritization fees recently paid to write the transaction's
+    /// accounts, from `getRecentPrioritizationFees`.
+    Auto,
+}
+
+impl FromStr for PriorityFee {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "auto" => Ok(PriorityFee::Auto),
+            micro_lamports => micro_lamports.parse().map(PriorityFee::MicroLamports)
+                .map_err(|_| format!("{value:?} is neither a price in micro-lamports nor \"auto\"")),
+        }
+    }
+}
+
+/// Compute budget a transaction requests and the priority fee it pays for it.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ComputeBudget {
+    /// Requested compute units, or the runtime default for the transaction.
+    pub compute_unit_limit: u32,
+    pub compute_unit_price_micro_lamports: u64,
+    /// `compute_unit_limit * compute_unit_price_micro_lamports`, rounded up to whole lamports.
+    pub priority_fee_lamports: u64,
+}
+
+/// What happened to a transaction built by one of the functions below.
+#[derive(Debug)]
+pub enum Outcome {
+    /// The transaction was signed, sent and confirmed.
+    Sent(Confirmed),
+    /// `--dry-run` was given; nothing was signed or sent.
+    DryRun(Box<DryRun>),
+    /// `--export-tx` or `--sign-only` was given; the transaction was written out instead of sent.
+    Exported(Box<Export>),
+}
+
+/// A confirmed transaction and where it landed.
+#[derive(Debug, Clone, Copy)]
+pub struct Confirmed {
+    pub signature: Signature,
+    /// Slot the transaction was processed in, if the RPC node still reports its status.
+    pub slot: Option<u64>,
+    /// Fee estimated before sending, including any priority fee.
+    pub fee_lamports: u64,
+}
+
+impl Confirmed {
+    fn new(connection: &Connection, signature: Signature, fee_lamports: u64) -> Confirmed {
+        // The slot is informational, so failing to look it up does not fail the command.
+        let slot = connection.with_retry("Fetching the transaction status", |rpc| rpc.get_signature_statuses(&[signature]))
+            .ok()
+            .and_then(|response| response.value.into_iter().next().flatten())
+            .map(|status| status.slot);
+        Confirmed { signature, slot, fee_lamports }
+    }
+}
+
+/// A transaction that has been estimated and then approved, simulated or set aside for export,
+/// but not sent yet.
+pub enum Prepared<'a> {
+    Approved(Approved<'a>),
+    DryRun(Box<DryRun>),
+    Export(Box<Export>),
+}
+
+/// A transaction to write out for signing elsewhere.
+#[derive(Debug)]
+pub struct Export {
+    /// File the transaction is written to, or `-` for stdout.
+    pub path: PathBuf,
+    pub transaction: ExportedTransaction,
+}
+
+/// A transaction the user approved, ready to be signed and sent.
+pub struct Approved<'a> {
+    signers: &'a Signers,
+    connection: &'a Connection,
+    message: Message,
+    /// Durable nonce account the message advances, if any.
+    nonce: Option<Pubkey>,
+    fee_lamports: u64,
+}
+
+impl Prepared<'_> {
+    /// Sign and send an approved transaction, or write out one set aside for export. A dry run is
+    /// passed through unchanged.
+    pub fn send(self) -> Result<Outcome, Error> {
+        match self {
+            Prepared::Approved(approved) => approved.send().map(Outcome::Sent),
+            Prepared::DryRun(dry_run) => Ok(Outcome::DryRun(dry_run)),
+            Prepared::Export(export) => {
+                export.transaction.write(&export.path)?;
+                Ok(Outcome::Exported(export))
+            }
+        }
+    }
+}
+
+impl Approved<'_> {
+    /// Sign with a fresh blockhash, or the nonce's current one, and send. Transient RPC failures
+    /// resend the same signed transaction; it is only re-signed once its blockhash has expired.
+    /// Program errors, and transactions that landed and failed, are returned at once.
+    fn send(mut self) -> Result<Confirmed, Error> {
+        let spinner = Progress::start("Sending Solana transaction..");
+        let policy = self.connection.retry;
+        let mut transaction = self.sign()?;
+        let mut attempt = 1;
+        loop {
+            match self.connection.send_and_confirm_transaction(&transaction) {
+                Ok(signature) => {
+                    spinner.finish();
+                    return Ok(Confirmed::new(self.connection, signature, self.fee_lamports))
+                }
+                Err(e) if attempt < policy.attempts && retry::is_retryable_rpc(&e) => {
+                    // Confirmation can fail for a transaction that still landed, so its status
+                    // decides whether sending again is safe.
+                    let signature = transaction.signatures[0];
+                    match self.connection.get_signature_status(&signature) {
+                        Ok(Some(Ok(()))) => {
+                            spinner.finish();
+                            return Ok(Confirmed::new(self.connection, signature, self.fee_lamports))
+                        }
+                        Ok(Some(Err(err))) => {
+                            return Err(program_error::decode(err.into(), &transaction.message, &self.connection.program_id))
+                        }
+                        Ok(None) | Err(_) => {}
+                    }
+                    let delay = policy.delay(attempt);
+                    let action = match self.expired(&transaction, &e)? {
+                        true => {
+                            transaction = self.sign()?;
+                            "its blockhash expired; re-signing and retrying"
+                        }
+                        false => "resending",
+                    };
+                    eprintln!("[WARN] Sending the transaction failed ({e}); {action} in {}ms (attempt {}/{})", delay.as_millis(), attempt + 1, policy.attempts);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(e) => return Err(program_error::decode(e, &transaction.message, &self.connection.program_id)),
+            }
+        }
+    }
+
+    /// Sign the message with the nonce's current blockhash, or a fresh one. Approval may have been
+    /// given a while ago, e.g. before a module upload, so the blockhash used for the estimate could
+    /// be close to expiring.
+    fn sign(&mut self) -> Result<Transaction, Error> {
+        let blockhash = match &self.nonce {
+            Some(nonce) => get_nonce(self.connection, nonce)?.data.blockhash(),
+            None => self.connection.with_retry("Fetching a recent blockhash", |rpc| rpc.get_latest_blockhash())
+                .map_err(|e| Error::rpc("Unable to fetch a recent blockhash").with_source(e))?,
+        };
+        self.message.recent_blockhash = blockhash;
+        Ok(Transaction::new(&self.signers.for_message(&self.message), self.message.clone(), blockhash))
+    }
+
+    /// Whether `transaction`, which failed to send with `err` and has not landed, can no longer be
+    /// processed: its blockhash is gone, or the nonce it used has advanced.
+    fn expired(&self, transaction: &Transaction, err: &ClientError) -> Result<bool, Error> {
+        if matches!(err.get_transaction_error(), Some(TransactionError::BlockhashNotFound)) {
+            return Ok(true)
+        }
+        let blockhash = transaction.message.recent_blockhash;
+        match &self.nonce {
+            Some(nonce) => Ok(get_nonce(self.connection, nonce)?.data.blockhash() != blockhash),
+            None => self.connection.with_retry("Checking the blockhash", |rpc| rpc.is_blockhash_valid(&blockhash, rpc.commitment()))
+                .map(|valid| !valid)
+                .map_err(|e| Error::rpc("Unable to check whether the transaction's blockhash is still valid").with_source(e)),
+        }
+    }
+}
+
+/// A program derived address and the seed it was derived from.
+#[derive(Debug, Serialize)]
+pub struct DerivedAddress {
+    pub seed: String,
+    pub address: String,
+    pub bump_seed: u8,
+}
+
+/// The transaction a command would send and how the cluster would process it.
+#[derive(Debug, Serialize)]
+pub struct DryRun {
+    pub fee_payer: String,
+    /// Owner of the accounts the transaction changes.
+    pub owner: String,
+    pub recent_blockhash: String,
+    /// Total fee, including the priority fee.
+    pub fee_lamports: u64,
+    pub compute_budget: ComputeBudget,
+    /// Total rent, the sum of `rent`.
+    pub rent_lamports: u64,
+    pub rent: Vec<AccountRent>,
+    /// Balance of the fee payer when the transaction was simulated.
+    pub payer_balance_lamports: u64,
+    pub derived_addresses: Vec<DerivedAddress>,
+    pub instructions: Vec<serde_json::Value>,
+    pub simulation: Simulation,
+}
+
+/// Result of `simulateTransaction`.
+#[derive(Debug, Serialize)]
+pub struct Simulation {
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+}
+
+/// The account-level parts of a transaction, shared by the functions that submit it.
+struct Plan<'a> {
+    /// Verb phrase for the confirmation prompt, e.g. "Deploy route operation".
+    action: &'a str,
+    /// Accounts the transaction creates or grows, whose rent the caller pays.
+    rent: Vec<AccountRent>,
+    /// Length of content carried in the instruction data, which shrinks the transaction byte for
+    /// byte, so the size limit error can say how much fits.
+    content_len: Option<usize>,
+    derived_addresses: Vec<DerivedAddress>,
+    instructions: Vec<Instruction>,
+}
+
+/// An account a transaction creates or grows, and the lamports it needs to stay rent exempt.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountRent {
+    /// What the account holds, e.g. "route data".
+    pub account: &'static str,
+    pub seed: String,
+    pub address: String,
+    /// Size of the account data once the transaction is processed.
+    pub size: usize,
+    /// Lamports the account holds before the transaction.
+    pub balance_lamports: u64,
+    /// Lamports the caller adds to the account.
+    pub lamports: u64,
+}
+
+/// Borsh-serialized size of `value`, which is how the rhizo program sizes its accounts.
+fn borsh_size(value: &impl BorshSerialize) -> usize {
+    value.try_to_vec().expect("account data can be serialized").len()
+}
+
+/// Rent owed for `address` to hold `size` bytes: the rent-exempt minimum less what `existing`
+/// already holds.
+fn account_rent(connection: &Connection, account: &'static str, seed: &str, address: &ProgramPubkey, existing: Option<&Account>, size: usize) -> Result<AccountRent, Error> {
+    let minimum = connection.with_retry("Fetching the rent-exempt minimum", |rpc| rpc.get_minimum_balance_for_rent_exemption(size))
+        .map_err(|e| Error::rpc("Unable to fetch the minimum balance for rent exemption").with_source(e))?;
+    let balance_lamports = existing.map_or(0, |existing| existing.lamports);
+    Ok(AccountRent {
+        account,
+        seed: seed.to_string(),
+        address: address.to_string(),
+        size,
+        balance_lamports,
+        lamports: minimum.saturating_sub(balance_lamports),
+    })
+}
+
+/// Fetch `address`, or `None` when the account does not exist.
+fn fetch_account(connection: &Connection, address: &ProgramPubkey) -> Result<Option<Account>, Error> {
+    connection.with_retry("Fetching account data", |rpc| rpc.get_account_with_commitment(address, rpc.commitment()))
+        .map_err(|e| Error::rpc(format!("Unable to fetch account {address}")).with_source(e))
+        .map(|response| response.value)
+}
+
+/// Rent for a developer's list of routes or signed on-chain bytes seeds once `seed` is on it.
+/// `DeveloperRoutes` and `DeveloperSignedOnchainBytes` both hold a single `Vec<String>`, so they
+/// share its Borsh layout.
+fn list_rent(connection: &Connection, account: &'static str, list_seed: &str, address: &ProgramPubkey, seed: &str) -> Result<AccountRent, Error> {
+    let existing = fetch_account(connection, address)?;
+    let mut list = existing.as_ref()
+        .and_then(|existing| Vec::<String>::deserialize(&mut existing.data.as_slice()).ok())
+        .unwrap_or_default();
+    if !list.iter().any(|entry| entry == seed) {
+        list.push(seed.to_string());
+    }
+    account_rent(connection, account, list_seed, address, existing.as_ref(), borsh_size(&list))
+}
+
+/// An RPC client that retries read-only calls according to the profile's retry policy.
+pub struct Connection {
+    rpc: RpcClient,
+    pub retry: RetryPolicy,
+    /// The profile's rhizo program, whose custom error codes failed transactions are decoded with.
+    pub program_id: Pubkey,
+}
+
+impl Deref for Connection {
+    type Target = RpcClient;
+
+    fn deref(&self) -> &RpcClient {
+        &self.rpc
+    }
+}
+
+impl Connection {
+    /// Call `f` until it succeeds or fails with an error that is not transient. Only use this for
+    /// calls that are safe to repeat.
+    pub fn with_retry<T>(&self, what: &str, f: impl Fn(&RpcClient) -> ClientResult<T>) -> ClientResult<T> {
+        self.retry.run(what, || f(&self.rpc), retry::is_retryable_rpc)
+    }
+}
+
+/// Connect to the profile's RPC endpoint, with the same CA file and pins as ingest endpoints.
+pub fn establish_connection(profile: &Profile) -> Result<Connection, Error> {
+    let http = reqwest::Client::builder()
+        .default_headers(HttpSender::default_headers())
+        .timeout(profile.retry.timeout)
+        .pool_idle_timeout(profile.retry.timeout)
+        .use_preconfigured_tls(tls::client_config(profile)?)
+        .build()
+        .map_err(|e| Error::tls("Unable to set up the RPC client").with_source(e))?;
+    let sender = HttpSender::new_with_client(profile.rpc_url.clone(), http);
+    Ok(Connection {
+        rpc: RpcClient::new_sender(sender, RpcClientConfig::with_commitment(profile.commitment)),
+        retry: profile.retry,
+        program_id: profile.program_id,
+    })
+}
+
+/// Derive the PDA the rhizo program stores `seed` under for `caller`.
+pub fn derive_address(seed: &str, caller: &Pubkey, program_pubkey: &Pubkey) -> Result<(ProgramPubkey, DerivedAddress), Error> {
+    let (address, bump_seed) = ProgramPubkey::try_find_program_address(&[seed.as_bytes(), caller.to_bytes().as_slice()], program_pubkey)
+        .ok_or_else(|| Error::validation(format!("Unable to derive a program address for {seed:?}; seeds can be at most {} bytes", solana_sdk::pubkey::MAX_SEED_LEN)))?;
+    Ok((address, DerivedAddress { seed: seed.to_string(), address: address.to_string(), bump_seed }))
 }
 
 pub fn alloc_ocb(
-    caller: &Keypair,
+    signers: &Signers,
     program_pubkey: &Pubkey,
-    connection: &RpcClient,
+    connection: &Connection,
+    options: &TransactionOptions,
     update_data: &rhizo_types::SignedOnchainBytesUpdate,
-) -> Result<(), Error> {
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Preparing Solana transaction..".into());
+) -> Result<Outcome, Error> {
+    let caller = signers.caller.as_ref();
     let seed = &update_data.seed;
     let seed = format!("socb-{seed}");
+    check_socb_size(update_data.bytes.inner.len())?;
 
-    let (_, bump_seed) = ProgramPubkey::find_program_address(
-        &[seed.as_bytes(),
-          caller.pubkey().to_bytes().as_slice()],
-          program_pubkey    
-    );
-    
-    let created_pda = ProgramPubkey::create_program_address(&[seed.as_bytes(), caller.pubkey().to_bytes().as_slice(), &[bump_seed]], program_pubkey).unwrap();
-  
+    let (created_pda, created) = derive_address(&seed, &caller.pubkey(), program_pubkey)?;
     let update_request = rhizo_types::SignedOnchainBytesUpdate {
         seed: seed.to_owned(),
         bytes: update_data.bytes.to_owned(),
-        bump_seed: Some(bump_seed),
+        bump_seed: Some(created.bump_seed),
     };
 
-
-    let (_, bump_seed) = ProgramPubkey::find_program_address(
-        &[b"_dev_socbs",
-          caller.pubkey().to_bytes().as_slice()],
-          program_pubkey    
-    );
-    
-    let list_pda = ProgramPubkey::create_program_address(&[b"_dev_socbs", caller.pubkey().to_bytes().as_slice(), &[bump_seed]], program_pubkey).unwrap();
-
+    let (list_pda, list) = derive_address("_dev_socbs", &caller.pubkey(), program_pubkey)?;
     let list_request = rhizo_types::ListSignedOnchainBytesUpdate {
         seed: seed.to_owned()
     };
 
-    let mut list_request_vec = 5u64.to_le_bytes().to_vec();
-    list_request_vec.extend(vec![bump_seed]);
+    let mut list_request_vec = vec![list.bump_seed];
     list_request_vec.extend(list_request.try_to_vec().expect("ocb can be listed"));
 
-    let mut update_request_vec = 2u64.to_le_bytes().to_vec(); // Instruction marker for update ocb
-    update_request_vec.extend(update_request.try_to_vec().expect("ocb update data can be serialized"));
-
     let request_update_instruction = Instruction {
         program_id: program_pubkey.to_owned(),
         accounts: vec![
@@ -76,7 +464,7 @@ pub fn alloc_ocb(
             AccountMeta { pubkey: created_pda, is_signer: false, is_writable: true },
             AccountMeta { pubkey: system_program::id(), is_signer: false, is_writable: false }
         ],
-        data: update_request_vec
+        data: instruction::data(instruction::ALLOC_SOCB, &update_request.try_to_vec().expect("ocb update data can be serialized")),
     };
 
     let list_socb_instruction = Instruction {
@@ -86,80 +474,109 @@ pub fn alloc_ocb(
             AccountMeta { pubkey: list_pda, is_signer: false, is_writable: true },
             AccountMeta { pubkey: system_program::id(), is_signer: false, is_writable: false }
         ],
-        data: list_request_vec
+        data: instruction::data(instruction::LIST_SOCB, &list_request_vec),
     };
 
-    let message = Message::new(&[
-                               list_socb_instruction.clone(),
-                               request_update_instruction.clone(), 
-    ], Some(&caller.pubkey()));
+    let bytes_account = fetch_account(connection, &created_pda)?;
+    let rent = vec![
+        account_rent(connection, "signed on-chain bytes", &seed, &created_pda, bytes_account.as_ref(), borsh_size(&update_request.bytes))?,
+        list_rent(connection, "developer signed on-chain bytes", "_dev_socbs", &list_pda, &seed)?,
+    ];
 
-    
-    let fee_lamports = connection.get_fee_calculator_for_blockhash(&connection.get_latest_blockhash().unwrap()).unwrap().unwrap().calculate_fee(&message);
-    let rent_exemption = connection.get_minimum_balance_for_rent_exemption(request_update_instruction.data.len()).unwrap();
-    spinner.stop_with_symbol("🗸");        
-    let confirmation = dialoguer::Confirm::new()
-        .with_prompt(format!("  Deploy route operation costs an estimated minimum of {:?} lamports + an additional {:?} to initialize a route keyed by {:?}, continue?", fee_lamports,rent_exemption, seed.clone()))
-        .interact()
-        .unwrap();
+    prepare(signers, connection, options, Plan {
+        action: "Allocate signed on-chain bytes operation",
+        rent,
+        content_len: Some(update_request.bytes.inner.len()),
+        derived_addresses: vec![created, list],
+        instructions: vec![list_socb_instruction, request_update_instruction],
+    })?.send()
+}
 
-    if !confirmation {
-        return Err(Error::new("Unable to submit transaction - cancelled due to user input"))
-    }
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Sending Solana transaction..".into());
-    
-    let transaction =
-        Transaction::new(&[caller], message, connection.get_latest_blockhash().expect("can fetch recent blockhash"));
-   
-
-    let try_transaction = connection.send_and_confirm_transaction(&transaction);
-    if try_transaction.is_err() {
-        println!("TransactionError {:?}", try_transaction);
-        let transaction_error = try_transaction.err().unwrap().get_transaction_error().unwrap();
-        let error_message = match transaction_error {
-            TransactionError::InstructionError(index, InstructionError::Custom(error_code)) => {
-                match (index, error_code) {
-                    (_, 0u32) => "Developer has reached the smart-contract's configured route limit.".to_string(),
-                    (_, 1u32) => "Nice try.".to_string(),
-                    _ => "Unsupported InstructionError code".to_string()
-                }
-            }
-            other => { other.to_string() }
-        };
-        return Err(Error::new(error_message.as_str()))
+/// Replace the content of signed on-chain bytes in one transaction. WriteSignedOnchainBytes takes
+/// no offset, so content that does not fit in a single transaction cannot be written in chunks.
+pub fn write_ocb(
+    signers: &Signers,
+    program_pubkey: &Pubkey,
+    connection: &Connection,
+    options: &TransactionOptions,
+    update_data: &rhizo_types::SignedOnchainBytesUpdate,
+) -> Result<Outcome, Error> {
+    send_ocb_write(signers, program_pubkey, connection, options, update_data, "Write signed on-chain bytes operation")
+}
+
+/// Grow or shrink signed on-chain bytes to `size` by writing them back zero-padded or truncated.
+/// Shrinking past the last non-zero byte needs `force`. The account does not record its written
+/// length, so that byte is only a guess at where the content ends: trailing zeros that belong to
+/// the content are not protected.
+pub fn resize_ocb(
+    signers: &Signers,
+    program_pubkey: &Pubkey,
+    connection: &Connection,
+    options: &TransactionOptions,
+    seed: &str,
+    size: usize,
+    force: bool,
+) -> Result<Outcome, Error> {
+    check_socb_size(size)?;
+    let caller = signers.caller.pubkey();
+    let prefixed = format!("socb-{seed}");
+    let (address, _) = derive_address(&prefixed, &caller, program_pubkey)?;
+    let account = fetch_account(connection, &address)?
+        .ok_or_else(|| Error::validation(format!("Signed on-chain bytes {seed:?} do not exist. Allocate them with `rhizo-cli socb alloc`.")))?;
+    let mut bytes = rhizo_types::SignedOnchainBytes::deserialize(&mut account.data.as_slice())
+        .map_err(|e| Error::rpc(format!("Unable to deserialize {prefixed:?} as SignedOnchainBytes")).with_source(e))?;
+
+    let current = bytes.inner.len();
+    // Best guess at the written length; see above.
+    let written = bytes.inner.iter().rposition(|byte| *byte != 0).map_or(0, |last| last + 1);
+    if size == current {
+        return Err(Error::validation(format!("Signed on-chain bytes {seed:?} already hold {size} bytes")))
     }
-    
-    spinner.stop_with_symbol("🗸");
+    if size < written && !force {
+        return Err(Error::validation(format!("Shrinking {seed:?} to {size} bytes would drop {} of the {written} bytes up to its last non-zero byte. Pass --force to truncate them.", written - size)))
+    }
+
+    let rent_for = |content: usize| connection.with_retry("Fetching the rent-exempt minimum", |rpc| rpc.get_minimum_balance_for_rent_exemption(content + 4 + 32))
+        .map_err(|e| Error::rpc("Unable to fetch the minimum balance for rent exemption").with_source(e));
+    let (before, after) = (rent_for(current)?, rent_for(size)?);
+    let difference = match after >= before {
+        true => format!("+{}", output::format_lamports(after - before)),
+        false => format!("-{}", output::format_lamports(before - after)),
+    };
+    output::note(&format!("Resizing {seed:?} from {current} to {size} bytes; the rent-exempt minimum changes by {difference}."));
 
-    Ok(()) 
+    bytes.inner.resize(size, 0);
+    let update_data = rhizo_types::SignedOnchainBytesUpdate { seed: seed.to_string(), bytes, bump_seed: None };
+    send_ocb_write(signers, program_pubkey, connection, options, &update_data, "Resize signed on-chain bytes operation")
 }
 
-pub fn write_ocb(
-    caller: &Keypair,
+/// Fail early when content of `len` bytes cannot fit in a signed on-chain bytes account.
+fn check_socb_size(len: usize) -> Result<(), Error> {
+    if len > MAX_SOCB_SIZE {
+        return Err(Error::validation(format!("{len} bytes exceed the {MAX_SOCB_SIZE}-byte maximum of a signed on-chain bytes account")))
+    }
+    Ok(())
+}
+
+fn send_ocb_write(
+    signers: &Signers,
     program_pubkey: &Pubkey,
-    connection: &RpcClient,
+    connection: &Connection,
+    options: &TransactionOptions,
     update_data: &rhizo_types::SignedOnchainBytesUpdate,
-) -> Result<(), Error> {
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Preparing Solana transaction..".into());
+    action: &str,
+) -> Result<Outcome, Error> {
+    let caller = signers.caller.as_ref();
     let seed = &update_data.seed;
     let seed = format!("socb-{seed}");
-    let (_, bump_seed) = ProgramPubkey::find_program_address(
-        &[seed.as_bytes(),
-          caller.pubkey().to_bytes().as_slice()],
-          program_pubkey    
-    );
-    
-    let created_pda = ProgramPubkey::create_program_address(&[seed.as_bytes(), caller.pubkey().to_bytes().as_slice(), &[bump_seed]], program_pubkey).unwrap();
-   
+
+    let (created_pda, created) = derive_address(&seed, &caller.pubkey(), program_pubkey)?;
     let update_request = rhizo_types::SignedOnchainBytesUpdate {
         seed: seed.to_owned(),
         bytes: update_data.bytes.to_owned(),
-        bump_seed: Some(bump_seed),
+        bump_seed: Some(created.bump_seed),
     };
 
-    let mut update_request_vec = 3u64.to_le_bytes().to_vec(); // Instruction marker for update ocb
-    update_request_vec.extend(update_request.try_to_vec().expect("ocb update data can be serialized"));
-
     let request_update_instruction = Instruction {
         program_id: program_pubkey.to_owned(),
         accounts: vec![
@@ -167,88 +584,40 @@ pub fn write_ocb(
             AccountMeta { pubkey: created_pda, is_signer: false, is_writable: true },
             AccountMeta { pubkey: system_program::id(), is_signer: false, is_writable: false }
         ],
-        data: update_request_vec
+        data: instruction::data(instruction::WRITE_SOCB, &update_request.try_to_vec().expect("ocb update data can be serialized")),
     };
 
-    let message = Message::new(&[
-                               request_update_instruction.clone(), 
-    ], Some(&caller.pubkey()));
-
-    
-    let fee_lamports = connection.get_fee_calculator_for_blockhash(&connection.get_latest_blockhash().unwrap()).unwrap().unwrap().calculate_fee(&message);
-    let rent_exemption = connection.get_minimum_balance_for_rent_exemption(request_update_instruction.data.len()).unwrap();
-    spinner.stop_with_symbol("🗸");        
-    let confirmation = dialoguer::Confirm::new()
-        .with_prompt(format!("  Deploy route operation costs an estimated minimum of {:?} lamports + an additional {:?} to initialize a route keyed by {:?}, continue?", fee_lamports,rent_exemption, seed.clone()))
-        .interact()
-        .unwrap();
-
-    if !confirmation {
-        return Err(Error::new("Unable to submit transaction - cancelled due to user input"))
-    }
-    
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Sending Solana transaction..".into());
-    
-    let transaction =
-        Transaction::new(&[caller], message, connection.get_latest_blockhash().expect("can fetch recent blockhash"));
-   
-    //println!("{:?}", bincode::serialize(&transaction).unwrap().to_base64(b64::URL_SAFE));
-
-    
-    let try_transaction = connection.send_and_confirm_transaction(&transaction);
-    if try_transaction.is_err() {
-        println!("TransactionError {:?}", try_transaction);
-        let transaction_error = try_transaction.err().unwrap().get_transaction_error().unwrap();
-        let error_message = match transaction_error {
-            TransactionError::InstructionError(index, InstructionError::Custom(error_code)) => {
-                match (index, error_code) {
-                    (_, 0u32) => "Developer has reached the smart-contract's configured route limit.".to_string(),
-                    (_, 1u32) => "Nice try.".to_string(),
-                    (_, 3u32) => "Not authorized to mutate bytes".to_string(),
-                    (_,_) => { "Unsupported error code".to_string()}
-                }
-            }
-            other => { other.to_string() }
-        };
-        return Err(Error::new(error_message.as_str()))
-    }
-    
-    spinner.stop_with_symbol("🗸");
+    let bytes_account = fetch_account(connection, &created_pda)?;
+    let rent = vec![account_rent(connection, "signed on-chain bytes", &seed, &created_pda, bytes_account.as_ref(), borsh_size(&update_request.bytes))?];
 
-    Ok(()) 
+    prepare(signers, connection, options, Plan {
+        action,
+        rent,
+        content_len: Some(update_request.bytes.inner.len()),
+        derived_addresses: vec![created],
+        instructions: vec![request_update_instruction],
+    })?.send()
 }
 
-pub fn update_route_data(
-    caller: &Keypair,
+/// Build and approve the route data transaction. It is returned unsent so `deploy` can upload the
+/// module first and the route never points at a module the ingest service does not have.
+pub fn update_route_data<'a>(
+    signers: &'a Signers,
     program_pubkey: &Pubkey,
-    connection: &RpcClient,
+    connection: &'a Connection,
+    options: &TransactionOptions,
     route_data: &rhizo_types::RouteData,
     operation_byte: u8,
-) -> Result<(), Error> {
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Preparing Solana transaction..".into());
-    let account_seed_string = &route_data.route.clone();
- 
-    let account_seed_string = account_seed_string;
-    let dev_routes_seed = b"_dev_routes";
-
-    let (_, bump_seed) = ProgramPubkey::find_program_address(
-        &[account_seed_string.as_bytes(),
-          caller.pubkey().to_bytes().as_slice()],
-          program_pubkey    
-    );
-
-    let (_, dev_routes_bump_seed) = ProgramPubkey::find_program_address(
-        &[dev_routes_seed,
-          caller.pubkey().to_bytes().as_slice()],
-          program_pubkey    
-    );
-
-    let created_pda = ProgramPubkey::create_program_address(&[account_seed_string.as_bytes(), caller.pubkey().to_bytes().as_slice(), &[bump_seed]], program_pubkey).unwrap();
-    let dev_routes_pda = ProgramPubkey::create_program_address(&[dev_routes_seed, caller.pubkey().to_bytes().as_slice(), &[dev_routes_bump_seed]], program_pubkey).unwrap();    
+) -> Result<Prepared<'a>, Error> {
+    let caller = signers.caller.as_ref();
+    let account_seed_string = &route_data.route;
+
+    let (created_pda, created) = derive_address(account_seed_string, &caller.pubkey(), program_pubkey)?;
+    let (dev_routes_pda, dev_routes) = derive_address("_dev_routes", &caller.pubkey(), program_pubkey)?;
 
     let request_update_data = rhizo_types::RouteUpdate {
         route: account_seed_string.to_owned(),
-        bump_seed: Some(dev_routes_bump_seed),
+        bump_seed: Some(dev_routes.bump_seed),
         operation: operation_byte,
     };
 
@@ -257,16 +626,10 @@ pub fn update_route_data(
         module_cid: route_data.module_cid,
         encodings: route_data.encodings.clone(),
         arguments: route_data.arguments.clone(),
-        bump_seed: Some(bump_seed),
+        bump_seed: Some(created.bump_seed),
         cache_config: route_data.cache_config,
     };
 
-    let mut request_update_data_vec = 1u64.to_le_bytes().to_vec(); // Instruction marker for route data    
-    let mut update_data_vec = 0u64.to_le_bytes().to_vec(); // Instruction marker for route data
-
-    request_update_data_vec.extend(request_update_data.try_to_vec().expect("request update can be serialized"));
-    update_data_vec.extend(updated_route_data.try_to_vec().expect("route data can be serialized"));
-
     let request_update_instruction = Instruction {
         program_id: program_pubkey.to_owned(),
         accounts: vec![
@@ -274,7 +637,7 @@ pub fn update_route_data(
             AccountMeta { pubkey: dev_routes_pda, is_signer: false, is_writable: true },
             AccountMeta { pubkey: system_program::id(), is_signer: false, is_writable: false }
         ],
-        data: request_update_data_vec
+        data: instruction::data(instruction::ROUTE_UPDATE_REQUEST, &request_update_data.try_to_vec().expect("request update can be serialized")),
     };
 
     let update_instruction = Instruction {
@@ -283,115 +646,59 @@ pub fn update_route_data(
             AccountMeta { pubkey: caller.pubkey(), is_signer: true, is_writable: true },
             AccountMeta { pubkey: created_pda, is_signer: false, is_writable: true },
             AccountMeta { pubkey: system_program::id(), is_signer: false, is_writable: false },
-            AccountMeta { pubkey: dev_routes_pda, is_signer: false, is_writable: false },            
+            AccountMeta { pubkey: dev_routes_pda, is_signer: false, is_writable: false },
         ],
-        data: update_data_vec
+        data: instruction::data(instruction::ROUTE_DATA, &updated_route_data.try_to_vec().expect("route data can be serialized")),
     };
-
-    let balance = connection.get_balance(&created_pda).unwrap();
-
-    let message = {
-        if request_update_data.operation == 0u8 {
-
-        Message::new(&[
-            request_update_instruction.clone(), 
-            update_instruction.clone()
-        ], Some(&caller.pubkey()))
-        } else {
-        let mut data = 4u64.to_le_bytes().to_vec();
-        data.extend(balance.to_le_bytes().to_vec());
+    let (instructions, rent) = if request_update_data.operation == 0u8 {
+        let route_account = fetch_account(connection, &created_pda)?;
+        let rent = vec![
+            account_rent(connection, "route data", account_seed_string, &created_pda, route_account.as_ref(), borsh_size(&updated_route_data))?,
+            list_rent(connection, "developer routes", "_dev_routes", &dev_routes_pda, account_seed_string)?,
+        ];
+        (vec![request_update_instruction, update_instruction], rent)
+    } else {
+        let balance = connection.with_retry("Fetching the route account balance", |rpc| rpc.get_balance(&created_pda))
+            .map_err(|e| Error::rpc("Unable to fetch the route account balance").with_source(e))?;
         let delete_instruction = Instruction {
-        program_id: program_pubkey.to_owned(),
-        accounts: vec![
-            AccountMeta { pubkey: caller.pubkey(), is_signer: true, is_writable: true },
-            AccountMeta { pubkey: created_pda, is_signer: false, is_writable: true },
-        ],
-        data,
+            program_id: program_pubkey.to_owned(),
+            accounts: vec![
+                AccountMeta { pubkey: caller.pubkey(), is_signer: true, is_writable: true },
+                AccountMeta { pubkey: created_pda, is_signer: false, is_writable: true },
+            ],
+            data: instruction::data(instruction::DELETE, &balance.to_le_bytes()),
         };
-            Message::new(&[
-            request_update_instruction.clone(), 
-            delete_instruction.clone()
-        ], Some(&caller.pubkey()))
-        }
+        (vec![request_update_instruction, delete_instruction], vec![])
     };
 
-    //if false{
-    
-    let fee_lamports = connection.get_fee_calculator_for_blockhash(&connection.get_latest_blockhash().unwrap()).unwrap().unwrap().calculate_fee(&message);
-    let rent_exemption = connection.get_minimum_balance_for_rent_exemption(update_instruction.data.len()).unwrap();
-    spinner.stop_with_symbol("🗸");        
-    let confirmation = dialoguer::Confirm::new()
-        .with_prompt(format!("  Deploy route operation costs an estimated minimum of {:?} lamports + an additional {:?} to initialize a route keyed by {:?}, continue?", fee_lamports,rent_exemption, account_seed_string.clone()))
-        .interact()
-        .unwrap();
-
-    if !confirmation {
-        return Err(Error::new("Unable to submit transaction - cancelled due to user input"))
-    }
-    //}
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Sending Solana transaction..".into());
-    
-    let transaction =
-        Transaction::new(&[caller], message, connection.get_latest_blockhash().expect("can fetch recent blockhash"));
-
-    let try_transaction = connection.send_and_confirm_transaction(&transaction);
-    if try_transaction.is_err() {
-        println!("TransactionError {:?}", try_transaction);
-        let transaction_error = try_transaction.err().unwrap().get_transaction_error().unwrap();
-        let error_message = match transaction_error {
-            TransactionError::InstructionError(index, InstructionError::Custom(error_code)) => {
-                match (index, error_code) {
-                    (_, 0u32) => "Developer has reached the smart-contract's configured route limit.".to_string(),
-                    (_, 1u32) => "Nice try.".to_string(),
-                    _ => "Unsupported InstructionError code".to_string()
-                }
-            }
-            other => { other.to_string() }
-        };
-        println!("[ERROR]: {error_message}");
-        return Err(Error::new(error_message.as_str()))
-    }
-    spinner.stop_with_symbol("🗸");
-    Ok(()) 
-    
+    prepare(signers, connection, options, Plan {
+        action: "Deploy route operation",
+        rent,
+        content_len: None,
+        derived_addresses: vec![created, dev_routes],
+        instructions,
+    })
 }
 
 pub fn yank_route(
-    caller: &Keypair,
+    signers: &Signers,
     program_pubkey: &Pubkey,
-    connection: &RpcClient,
+    connection: &Connection,
+    options: &TransactionOptions,
     account_seed_string: &str,
-) -> Result<(), Error> {
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Preparing Solana transaction..".into());
-    let dev_routes_seed = b"_dev_routes";
-
+) -> Result<Outcome, Error> {
+    let caller = signers.caller.as_ref();
     let account_seed_string = format!("route-{account_seed_string}");
 
-    let (_, bump_seed) = ProgramPubkey::find_program_address(
-        &[account_seed_string.as_bytes(),
-          caller.pubkey().to_bytes().as_slice()],
-          program_pubkey    
-    );
-
-    let (_, dev_routes_bump_seed) = ProgramPubkey::find_program_address(
-        &[dev_routes_seed,
-          caller.pubkey().to_bytes().as_slice()],
-          program_pubkey    
-    );
-
-    let created_pda = ProgramPubkey::create_program_address(&[account_seed_string.as_bytes(), caller.pubkey().to_bytes().as_slice(), &[bump_seed]], program_pubkey).unwrap();
-    let dev_routes_pda = ProgramPubkey::create_program_address(&[dev_routes_seed, caller.pubkey().to_bytes().as_slice(), &[dev_routes_bump_seed]], program_pubkey).unwrap();    
+    let (created_pda, created) = derive_address(&account_seed_string, &caller.pubkey(), program_pubkey)?;
+    let (dev_routes_pda, dev_routes) = derive_address("_dev_routes", &caller.pubkey(), program_pubkey)?;
 
     let request_update_data = rhizo_types::RouteUpdate {
         route: account_seed_string.to_owned(),
-        bump_seed: Some(dev_routes_bump_seed),
+        bump_seed: Some(dev_routes.bump_seed),
         operation: 1u8, //deprecated
     };
 
-    let mut request_update_data_vec = 6u64.to_le_bytes().to_vec(); // Instruction marker for route data    
-
-    request_update_data_vec.extend(request_update_data.try_to_vec().expect("request update can be serialized"));
-
     let request_update_instruction = Instruction {
         program_id: program_pubkey.to_owned(),
         accounts: vec![
@@ -400,49 +707,362 @@ pub fn yank_route(
             AccountMeta { pubkey: created_pda, is_signer: false, is_writable: true},
             AccountMeta { pubkey: system_program::id(), is_signer: false, is_writable: false }
         ],
-        data: request_update_data_vec
+        data: instruction::data(instruction::YANK, &request_update_data.try_to_vec().expect("request update can be serialized")),
+    };
+
+    prepare(signers, connection, options, Plan {
+        action: "Yank route operation",
+        rent: vec![],
+        content_len: None,
+        derived_addresses: vec![created, dev_routes],
+        instructions: vec![request_update_instruction],
+    })?.send()
+}
+
+/// Estimate the fee and rent of `plan`, then simulate it (`--dry-run`), set it aside for export
+/// (`--export-tx`) or ask for approval.
+fn prepare<'a>(signers: &'a Signers, connection: &'a Connection, options: &TransactionOptions, plan: Plan) -> Result<Prepared<'a>, Error> {
+    let spinner = Progress::start("Preparing Solana transaction..");
+    let caller = signers.caller.as_ref();
+    let payer = signers.fee_payer().pubkey();
+    let nonce = match &options.nonce {
+        Some(address) => {
+            let data = get_nonce(connection, address)?.data;
+            let authority = signers.nonce_authority().pubkey();
+            if data.authority != authority {
+                return Err(Error::validation(format!("The authority of nonce account {address} is {}, not {authority}. Pass --nonce-authority.", data.authority)))
+            }
+            Some((system_instruction::advance_nonce_account(address, &authority), data.blockhash()))
+        }
+        None => None,
+    };
+    let rent_lamports: u64 = plan.rent.iter().map(|rent| rent.lamports).sum();
+    // Accounts are funded from the caller, so a separate fee payer covers the rent by transferring
+    // it to the caller first.
+    let rent_transfer = (payer != caller.pubkey() && rent_lamports > 0)
+        .then(|| system_instruction::transfer(&payer, &caller.pubkey(), rent_lamports));
+
+    let nonced = nonce.is_some();
+    let (advance, nonce_blockhash) = nonce.unzip();
+    let mut instructions: Vec<Instruction> = advance.into_iter().chain(rent_transfer).chain(plan.instructions).collect();
+    let (budget, budget_instructions) = compute_budget(connection, options, &instructions)?;
+
+    let latest_blockhash = connection.with_retry("Fetching a recent blockhash", |rpc| rpc.get_latest_blockhash())
+        .map_err(|e| Error::rpc("Unable to fetch a recent blockhash").with_source(e))?;
+    // The signature fee does not depend on the compute budget, so it is estimated without the
+    // ComputeBudget instructions and the priority fee is added here, whatever the RPC node's version.
+    // Nodes only price messages with a recent blockhash, so a nonce's is swapped in afterwards.
+    let unbudgeted = Message::new_with_blockhash(&instructions, Some(&payer), &latest_blockhash);
+    let signature_fee = connection.with_retry("Estimating the transaction fee", |rpc| rpc.get_fee_for_message(&unbudgeted))
+        .map_err(|e| Error::rpc("Unable to estimate the transaction fee").with_source(e))?;
+    let fee_lamports = signature_fee + budget.priority_fee_lamports;
+
+    // The runtime only honours a nonce when AdvanceNonceAccount is the first instruction.
+    let at = usize::from(nonced);
+    instructions.splice(at..at, budget_instructions);
+    let blockhash = nonce_blockhash.unwrap_or(latest_blockhash);
+    let message = Message::new_with_blockhash(&instructions, Some(&payer), &blockhash);
+
+    let size = bincode::serialized_size(&Transaction::new_unsigned(message.clone())).expect("transaction can be serialized") as usize;
+    if size > PACKET_DATA_SIZE {
+        let over = size - PACKET_DATA_SIZE;
+        let mut message = format!("The transaction is {size} bytes, {over} over the {PACKET_DATA_SIZE}-byte limit");
+        if let Some(len) = plan.content_len {
+            // WriteSignedOnchainBytes replaces the whole content and takes no offset, so content
+            // cannot be split across transactions.
+            message.push_str(&format!("; at most {} bytes of content fit in one transaction with these options, and the rhizo program cannot write signed on-chain bytes in chunks", len.saturating_sub(over)));
+        }
+        return Err(Error::validation(message))
+    }
+
+    let balance = connection.with_retry("Fetching the fee payer's balance", |rpc| rpc.get_balance(&payer))
+        .map_err(|e| Error::rpc(format!("Unable to fetch the balance of {payer}")).with_source(e))?;
+    let needed = fee_lamports + rent_lamports;
+    let insufficient = Error::validation(format!("insufficient funds: need {}, have {} in {payer}", output::format_lamports(needed), output::format_lamports(balance)));
+
+    if options.dry_run {
+        let simulation = simulate(connection, Transaction::new_unsigned(message))?;
+        spinner.finish();
+        return Ok(Prepared::DryRun(Box::new(DryRun {
+            fee_payer: payer.to_string(),
+            owner: caller.pubkey().to_string(),
+            recent_blockhash: blockhash.to_string(),
+            fee_lamports,
+            compute_budget: budget,
+            rent_lamports,
+            rent: plan.rent,
+            payer_balance_lamports: balance,
+            derived_addresses: plan.derived_addresses,
+            instructions: instructions.iter().map(instruction::describe).collect(),
+            simulation,
+        })))
+    }
+    spinner.finish();
+
+    if let Some(path) = &options.export_tx {
+        // Exporting spends nothing, so only the ceilings are checked; `sign` asks for approval.
+        confirm_transaction(&TransactionOptions { yes: true, ..options.clone() }, fee_lamports, rent_lamports, String::new())?;
+        if balance < needed {
+            // The payer can still be funded before the transaction is submitted.
+            eprintln!("[WARN] {insufficient}. Fund it before submitting the transaction.");
+        }
+        let mut transaction = ExportedTransaction::new(plan.action, &message, fee_lamports, rent_lamports);
+        if options.sign_only {
+            for signer in signers.for_message(&message) {
+                transaction.sign(signer)?;
+            }
+        }
+        return Ok(Prepared::Export(Box::new(Export { path: path.clone(), transaction })))
+    }
+
+    if balance < needed {
+        return Err(insufficient)
+    }
+
+    let mut prompt = match payer == caller.pubkey() {
+        true => format!("  Fee payer and owner: {payer}"),
+        false => format!("  Fee payer: {payer} (pays the fee and rent)\n  Owner: {}", caller.pubkey()),
     };
+    prompt.push_str(&format!("\n  {} costs an estimated:", plan.action));
+    prompt.push_str(&format!("\n    Fee\t\t{}", output::format_lamports(fee_lamports)));
+    if budget.priority_fee_lamports > 0 {
+        prompt.push_str(&format!(", including a {} lamport priority fee", budget.priority_fee_lamports));
+    }
+    for rent in &plan.rent {
+        prompt.push_str(&format!("\n    Rent\t{} for the {} account {:?} ({} bytes)", output::format_lamports(rent.lamports), rent.account, rent.seed, rent.size));
+    }
+    prompt.push_str(&format!("\n    Total\t{}", output::format_lamports(needed)));
+    prompt.push_str(&format!("\n  Balance of {payer}: {}. Continue?", output::format_lamports(balance)));
+    confirm_transaction(options, fee_lamports, rent_lamports, prompt)?;
+    Ok(Prepared::Approved(Approved { signers, connection, message, nonce: options.nonce, fee_lamports }))
+}
+
+/// ComputeBudget instructions for the options given, and what they cost. The price for
+/// `--priority-fee auto` is looked up for the accounts `instructions` write.
+fn compute_budget(connection: &Connection, options: &TransactionOptions, instructions: &[Instruction]) -> Result<(ComputeBudget, Vec<Instruction>), Error> {
+    let price = match options.priority_fee {
+        None => 0,
+        Some(PriorityFee::MicroLamports(price)) => price,
+        Some(PriorityFee::Auto) => {
+            let mut writable: Vec<Pubkey> = instructions.iter()
+                .flat_map(|instruction| instruction.accounts.iter())
+                .filter(|meta| meta.is_writable)
+                .map(|meta| meta.pubkey)
+                .collect();
+            writable.sort();
+            writable.dedup();
+            let mut fees: Vec<u64> = connection.with_retry("Fetching recent prioritization fees", |rpc| rpc.get_recent_prioritization_fees(&writable))
+                .map_err(|e| Error::rpc("Unable to fetch recent prioritization fees").with_source(e))?
+                .into_iter()
+                .map(|fee| fee.prioritization_fee)
+                .collect();
+            fees.sort_unstable();
+            // Nearest-rank 75th percentile; slots without priority fees count as zero.
+            fees.get((fees.len() * 3).div_ceil(4).saturating_sub(1)).copied().unwrap_or_default()
+        }
+    };
+
+    let mut budget_instructions = vec![];
+    if let Some(limit) = options.compute_unit_limit {
+        budget_instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(limit));
+    }
+    if price > 0 {
+        budget_instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    }
+    let compute_unit_limit = options.compute_unit_limit
+        .unwrap_or_else(|| (instructions.len() as u32).saturating_mul(DEFAULT_UNITS_PER_INSTRUCTION).min(MAX_COMPUTE_UNIT_LIMIT));
+    let priority_fee_lamports = (compute_unit_limit as u128 * price as u128).div_ceil(1_000_000) as u64;
+    let budget = ComputeBudget { compute_unit_limit, compute_unit_price_micro_lamports: price, priority_fee_lamports };
+    Ok((budget, budget_instructions))
+}
+
+/// Address of the nonce account `nonce create --seed <seed>` creates for `base`.
+pub fn nonce_address(base: &Pubkey, seed: &str) -> Result<Pubkey, Error> {
+    Pubkey::create_with_seed(base, seed, &system_program::id())
+        .map_err(|e| Error::validation(format!("Unable to derive a nonce account address from seed {seed:?}")).with_source(e))
+}
+
+/// Create a durable nonce account at the address derived from the caller and `seed`, with the
+/// caller as its authority. It is funded with the rent-exempt minimum unless `lamports` is given.
+pub fn create_nonce_account(
+    signers: &Signers,
+    connection: &Connection,
+    options: &TransactionOptions,
+    seed: &str,
+    lamports: Option<u64>,
+) -> Result<Outcome, Error> {
+    let caller = signers.caller.as_ref();
+    let address = nonce_address(&caller.pubkey(), seed)?;
+    let minimum = connection.with_retry("Fetching the rent-exempt minimum", |rpc| rpc.get_minimum_balance_for_rent_exemption(NonceState::size()))
+        .map_err(|e| Error::rpc("Unable to fetch the minimum balance for rent exemption").with_source(e))?;
+    let lamports = lamports.unwrap_or(minimum);
+    if lamports < minimum {
+        return Err(Error::validation(format!("A nonce account needs at least {minimum} lamports to be rent exempt")))
+    }
+    let instructions = system_instruction::create_nonce_account_with_seed(&caller.pubkey(), &address, &caller.pubkey(), seed, &caller.pubkey(), lamports);
+    // The account is funded with exactly `lamports`, which may exceed the minimum.
+    let rent = AccountRent {
+        account: "nonce",
+        seed: seed.to_string(),
+        address: address.to_string(),
+        size: NonceState::size(),
+        balance_lamports: 0,
+        lamports,
+    };
+
+    prepare(signers, connection, options, Plan {
+        action: "Create nonce account operation",
+        rent: vec![rent],
+        content_len: None,
+        derived_addresses: vec![],
+        instructions,
+    })?.send()
+}
+
+/// Withdraw `lamports` from a nonce account to `to`. Withdrawing the whole balance closes it.
+pub fn withdraw_nonce_account(
+    signers: &Signers,
+    connection: &Connection,
+    options: &TransactionOptions,
+    address: &Pubkey,
+    to: &Pubkey,
+    lamports: u64,
+) -> Result<Outcome, Error> {
+    let nonce = get_nonce(connection, address)?;
+    let authority = signers.nonce_authority().pubkey();
+    if nonce.data.authority != authority {
+        return Err(Error::validation(format!("The authority of nonce account {address} is {}, not {authority}. Pass --nonce-authority.", nonce.data.authority)))
+    }
+    if lamports > nonce.lamports {
+        return Err(Error::validation(format!("Nonce account {address} only holds {} lamports", nonce.lamports)))
+    }
 
-    let message = Message::new(&[
-            request_update_instruction.clone(), 
-    ], Some(&caller.pubkey()));
+    prepare(signers, connection, options, Plan {
+        action: "Withdraw from nonce account operation",
+        rent: vec![],
+        content_len: None,
+        derived_addresses: vec![],
+        instructions: vec![system_instruction::withdraw_nonce_account(address, &authority, to, lamports)],
+    })?.send()
+}
+
+/// A durable nonce account's balance and state.
+pub struct NonceAccount {
+    pub lamports: u64,
+    pub data: NonceData,
+}
+
+/// Fetch an initialized durable nonce account.
+pub fn get_nonce(connection: &Connection, address: &Pubkey) -> Result<NonceAccount, Error> {
+    let account = connection.with_retry("Fetching the nonce account", |rpc| rpc.get_account_with_commitment(address, rpc.commitment()))
+        .map_err(|e| Error::rpc(format!("Unable to fetch nonce account {address}")).with_source(e))?
+        .value
+        .ok_or_else(|| Error::validation(format!("Nonce account {address} does not exist. Create one with `rhizo-cli nonce create`.")))?;
+    let data = nonce_utils::account_identity_ok(&account)
+        .and_then(|_| nonce_utils::data_from_account(&account))
+        .map_err(|e| Error::validation(format!("{address} is not an initialized nonce account")).with_source(e))?;
+    Ok(NonceAccount { lamports: account.lamports, data })
+}
+
+/// Like [`get_account_data`], but `None` when the account does not exist.
+pub fn find_account_data(connection: &Connection, caller: &Pubkey, program_pubkey: &Pubkey, seed: &str) -> Result<Option<Vec<u8>>, Error> {
+    let (address, _) = derive_address(seed, caller, program_pubkey)?;
+    let account = connection.with_retry("Fetching account data", |rpc| rpc.get_account_with_commitment(&address, rpc.commitment()))
+        .map_err(|e| Error::rpc(format!("Unable to fetch account data for {seed:?}")).with_source(e))?
+        .value;
+    Ok(account.map(|account| account.data))
+}
 
-    
-    let fee_lamports = connection.get_fee_calculator_for_blockhash(&connection.get_latest_blockhash().unwrap()).unwrap().unwrap().calculate_fee(&message);
-    spinner.stop_with_symbol("🗸");        
+/// Fetch the data of the account the rhizo program stores `seed` under for `caller`.
+pub fn get_account_data(connection: &Connection, caller: &Pubkey, program_pubkey: &Pubkey, seed: &str) -> Result<Vec<u8>, Error> {
+    let (address, _) = derive_address(seed, caller, program_pubkey)?;
+    connection.with_retry("Fetching account data", |rpc| rpc.get_account_data(&address))
+        .map_err(|e| Error::rpc(format!("Unable to fetch account data for {seed:?}")).with_source(e))
+}
+
+/// Run `simulateTransaction` on an unsigned transaction. Signature verification is skipped, so
+/// nothing needs to be signed.
+fn simulate(connection: &Connection, transaction: Transaction) -> Result<Simulation, Error> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(connection.commitment()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = connection.with_retry("Simulating the transaction", |rpc| rpc.simulate_transaction_with_config(&transaction, config.clone()))
+        .map_err(|e| Error::rpc("Unable to simulate the transaction").with_source(e))?
+        .value;
+    Ok(Simulation {
+        err: result.err.map(|e| e.to_string()),
+        logs: result.logs.unwrap_or_default(),
+        units_consumed: result.units_consumed,
+    })
+}
+
+/// Check the estimate against the configured ceilings, then ask the user to approve the transaction
+/// unless `--yes` was given. Fails instead of prompting when no terminal is attached or in JSON mode.
+pub fn confirm_transaction(options: &TransactionOptions, fee_lamports: u64, rent_lamports: u64, prompt: String) -> Result<(), Error> {
+    if let Some(max_fee) = options.max_fee_lamports {
+        if fee_lamports > max_fee {
+            return Err(Error::validation(format!("Estimated fee of {fee_lamports} lamports exceeds the --max-fee-lamports limit of {max_fee}")))
+        }
+    }
+    if let Some(max_rent) = options.max_rent_lamports {
+        if rent_lamports > max_rent {
+            return Err(Error::validation(format!("Estimated rent of {rent_lamports} lamports exceeds the --max-rent-lamports limit of {max_rent}")))
+        }
+    }
+    if options.yes {
+        return Ok(())
+    }
+    if output::is_json() || !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Err(Error::cancelled("Transaction requires confirmation but no interactive terminal is available. Pass --yes or set RHIZO_YES=1 to approve it."))
+    }
     let confirmation = dialoguer::Confirm::new()
-        .with_prompt(format!("  Deploy route operation costs an estimated minimum of {:?} lamports, continue?", fee_lamports))
+        .with_prompt(prompt)
         .interact()
-        .unwrap();
+        .map_err(|e| Error::io("Unable to read confirmation from the terminal").with_source(e))?;
 
     if !confirmation {
-        return Err(Error::new("Unable to submit transaction - cancelled due to user input"))
-    }
-    
-    let mut spinner = spinners::Spinner::new(spinners::Spinners::Dots8Bit, "Sending Solana transaction..".into());
-    
-    let transaction =
-        Transaction::new(&[caller], message, connection.get_latest_blockhash().expect("can fetch recent blockhash"));
-
-    let try_transaction = connection.send_and_confirm_transaction(&transaction);
-    if try_transaction.is_err() {
-        println!("TransactionError {:?}", try_transaction);
-        let transaction_error = try_transaction.err().unwrap().get_transaction_error().unwrap();
-        let error_message = match transaction_error {
-            TransactionError::InstructionError(index, InstructionError::Custom(error_code)) => {
-                match (index, error_code) {
-                    (_, 0u32) => "Developer has reached the smart-contract's configured route limit.".to_string(),
-                    (_, 1u32) => "Nice try.".to_string(),
-                    _ => "Unsupported InstructionError code".to_string()
-                }
+        return Err(Error::cancelled("Unable to submit transaction - cancelled due to user input"))
+    }
+    Ok(())
+}
+
+/// Send a transaction signed elsewhere and wait for confirmation. It cannot be re-signed with a
+/// fresh blockhash, but sending the same signed transaction again cannot apply it twice, so
+/// transient failures are retried as they are. `fee_lamports` is the fee estimated at export.
+pub fn submit(connection: &Connection, transaction: &Transaction, fee_lamports: u64) -> Result<Confirmed, Error> {
+    let blockhash = transaction.message.recent_blockhash;
+    match nonce_account(transaction) {
+        Some(address) => {
+            let current = get_nonce(connection, &address)?.data.blockhash();
+            if current != blockhash {
+                return Err(Error::validation(format!("Nonce account {address} has advanced since the transaction was exported, so it can no longer be processed. Export and sign it again.")))
             }
-            other => { other.to_string() }
-        };
-        println!("[ERROR]: {error_message}");
-        return Err(Error::new(error_message.as_str()))
+        }
+        None => {
+            let valid = connection.with_retry("Checking the blockhash", |rpc| rpc.is_blockhash_valid(&blockhash, rpc.commitment()))
+                .map_err(|e| Error::rpc("Unable to check whether the transaction's blockhash is still valid").with_source(e))?;
+            if !valid {
+                return Err(Error::validation(format!("The transaction's blockhash {blockhash} has expired. Export and sign it again.")))
+            }
+        }
     }
-    spinner.stop_with_symbol("🗸");
-    Ok(()) 
-    
+    let spinner = Progress::start("Sending Solana transaction..");
+    let signature = connection.retry
+        .run("Sending the transaction", || connection.send_and_confirm_transaction(transaction), |e| retry::is_retryable_rpc(e) && !retry::is_blockhash_expired(e))
+        .map_err(|e| match retry::is_blockhash_expired(&e) {
+            true => Error::validation(format!("The transaction's blockhash {blockhash} expired before it was confirmed. Export and sign it again.")).with_source(e),
+            false => program_error::decode(e, &transaction.message, &connection.program_id),
+        })?;
+    spinner.finish();
+    Ok(Confirmed::new(connection, signature, fee_lamports))
 }
 
+/// The durable nonce account a transaction advances, if it uses one.
+pub fn nonce_account(transaction: &Transaction) -> Option<Pubkey> {
+    let advance = solana_sdk::transaction::uses_durable_nonce(transaction)?;
+    let index = *advance.accounts.first()?;
+    transaction.message.account_keys.get(index as usize).copied()
+}
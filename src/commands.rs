@@ -1,184 +1,247 @@
-use borsh::{BorshSerialize, BorshDeserialize};
-use crate::{client, error::RhizoCLIError as Error, utils};
-use crate::types::Config;
-use hyper::{Body, Client, Request, StatusCode};
-use hyper::header::CONTENT_TYPE;
-use rhizo_types::Encoding::*;
+use base64::Engine;
+use borsh::BorshDeserialize;
+use crate::{client, error::RhizoCLIError as Error, ingest, instruction, journal, offline, output, program_error, schema, utils};
+use crate::error::TomlError;
+use crate::profile::{self, Profile, ProfileOverrides};
+use crate::client::{Confirmed, Outcome, TransactionOptions};
+use crate::cli::{Collection, ContentFormat, NonceAccountArgs, SocbContentArgs, SocbDisplayArgs, UploadHeaderArgs};
+use crate::output::Progress;
+use crate::types::{self, Config};
+use clap::ValueEnum;
+use serde_json::json;
 use solana_sdk::{pubkey::Pubkey, signature::Signer};
-use solana_program::pubkey::Pubkey as ProgramPubkey;
 use std::io::Write;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use spinners::{Spinner, Spinners};
 use wasmer::{Module, Store};
 use wasmer_wasix::Pipe;
 use wasmer_wasix::{WasiEnvBuilder, capabilities::Capabilities, http::HttpClientCapabilityV1, capabilities::CapabilityThreadingV1};
 
-pub fn yank_route(seed: &str) -> Result<(), Error> {
-    let keypair = utils::get_keypair()?;
-    let program_pubkey = Pubkey::from_str("Ep1SV45cqumZmogwWFy6pVNvMpRerMZUUhSJTbTh2e58");
+pub fn yank_route(profile: &Profile, seed: &str, options: &TransactionOptions) -> Result<(), Error> {
+    let signers = utils::get_signers(profile, options)?;
+    let connection = client::establish_connection(profile)?;
 
-    if program_pubkey.is_err() {        
-        return Err(Error::new("Unable to create pubkey for pre-configured program address"))
-    }
-
-    let connection = client::establish_connection()?;
-
-    client::yank_route(&keypair, &program_pubkey.unwrap(), &connection, seed).unwrap();
+    let outcome = client::yank_route(&signers, &profile.program_id, &connection, options, seed)?;
+    report(profile, "yank", Some(seed), &outcome);
     Ok(())
 }
 
-pub fn test_module(path: &str){
-    println!("WARNING: Failing to use rhizo_sdk functions like tprintln and assert_eq as the exclusive methods to print output and assert within your test function may result in hanging tests.");
+pub fn test_module(path: &Path) -> Result<(), Error> {
+    output::note("WARNING: Failing to use rhizo_sdk functions like tprintln and assert_eq as the exclusive methods to print output and assert within your test function may result in hanging tests.");
     let mut store = Store::default();
-    let mut module = Module::from_file(&store, path).expect("Failed to read Wasm module");
+    let module = Module::from_file(&store, path)
+        .map_err(|e| Error::wasm("Unable to read file at provided path as a valid WASM module").with_source(e))?;
 
-    let (mut stdin_tx, stdin_rx) = Pipe::channel();    
-    let (stderr_tx, mut stderr_rx) = Pipe::channel();
-    let (stdout_tx, mut stdout_rx) = Pipe::channel();
+    let (mut stdin_tx, _stdin_rx) = Pipe::channel();
 
     let argument_buffer = Vec::<u8>::new();
     let len_bytes = (argument_buffer.len() as i32).to_le_bytes();
     let concatenated = [len_bytes.to_vec(), argument_buffer].concat();
-    stdin_tx.write_all(concatenated.as_slice()).unwrap();
-    stdin_tx.flush().unwrap();
+    stdin_tx.write_all(concatenated.as_slice())
+        .and_then(|_| stdin_tx.flush())
+        .map_err(|e| Error::io("Unable to write arguments to the module's stdin").with_source(e))?;
 
-    let wasi_env_builder = WasiEnvBuilder::new("wasm")
+    let mut wasi_env_builder = WasiEnvBuilder::new("wasm")
         .capabilities(
         Capabilities {
             insecure_allow_all: true,
             http_client: HttpClientCapabilityV1::new_allow_all(),
             threading: CapabilityThreadingV1 { max_threads: None, enable_asynchronous_threading: true },
     });
-    let (mut instance, mut fn_env) = wasi_env_builder.instantiate(module.clone(), &mut store).unwrap();        
-    let try_get_start_fn = instance.exports.get_function("test");
-    if try_get_start_fn.is_err() {
-        println!("test() function not found in the provided module. Check that #[no_mangle] was defined above the function implementation.");
-        return
-    }
-    let start_fn = try_get_start_fn.unwrap();
-    
-    let invoke_res = start_fn.call(&mut store, &[]); 
-    
-    if invoke_res.is_err(){
-        println!("Failed module tests.");
-        return
-    }
-        
-    println!("All assertions passed.");
-}
-
-pub fn view(collection: &str, seed: &str) -> Result<(), Error> {
-    let mut spinner = Spinner::new(Spinners::Dots8Bit, format!("Fetching {} config..", collection).into());
-    let connection = client::establish_connection()?;
-    let keypair = utils::get_keypair()?;
-
-    let program_pubkey = match Pubkey::from_str(
-        "Ep1SV45cqumZmogwWFy6pVNvMpRerMZUUhSJTbTh2e58"
-    ) {
-        Ok(pubkey) => pubkey,
-        _ => {
-            return Err(Error::new("Unable to create a pubkey for the program address"));
-        }
-    };
-
-    let seed = format!("{collection}-{seed}");
-
-    let (_, bump_seed) = ProgramPubkey::find_program_address(
-        &[seed.as_bytes(),
-          keypair.pubkey().to_bytes().as_slice()],
-          &program_pubkey    
-    );
-
-    let pda_address = ProgramPubkey::create_program_address(&[seed.as_bytes(), keypair.pubkey().to_bytes().as_slice(), &[bump_seed]], &program_pubkey);
-
-    if pda_address.is_err() {
-        return Err(Error::new("Unable to create a PDA the view command"));
+    if output::is_json() {
+        // Keep the module's own output off stdout so the JSON document stays parseable.
+        wasi_env_builder = wasi_env_builder.stdout(Box::new(wasmer_wasix::virtual_fs::host_fs::Stderr::default()));
     }
+    let (instance, _) = wasi_env_builder.instantiate(module, &mut store)
+        .map_err(|e| Error::wasm("Unable to instantiate the provided module as a WASIX module").with_source(e))?;
+    let test_fn = instance.exports.get_function("test")
+        .map_err(|e| Error::wasm("test() function not found in the provided module. Check that #[no_mangle] was defined above the function implementation.").with_source(e))?;
+    test_fn.call(&mut store, &[])
+        .map_err(|e| Error::validation("Failed module tests.").with_source(e))?;
 
-    let account_data = connection.get_account_data(&pda_address.clone().unwrap());
-    if account_data.is_err(){
-        return Err(Error::new("Unable to fetch account data"))
+    if output::is_json() {
+        output::print_json(&json!({ "passed": true }));
+    } else {
+        println!("All assertions passed.");
     }
-    let account_data = account_data.unwrap();
+    Ok(())
+}
 
-    let mut buffer = account_data.as_slice();
-    
+pub fn view_route(profile: &Profile, seed: &str) -> Result<(), Error> {
+    let spinner = Progress::start("Fetching route config..");
+    let connection = client::establish_connection(profile)?;
+    let keypair = utils::get_keypair(profile)?;
+    let account_data = client::get_account_data(&connection, &keypair.pubkey(), &profile.program_id, &format!("route-{seed}"))?;
+    spinner.finish();
 
-    spinner.stop_with_symbol("🗸");
-    if collection.eq("route"){
-    let deserialized = match rhizo_types::RouteData::deserialize(&mut buffer) {
+    let deserialized = match rhizo_types::RouteData::deserialize(&mut account_data.as_slice()) {
         Ok(route_data) => route_data, 
-        _ => return Err(Error::new("Unable to deserialize account data as RouteData")),
+        Err(e) => return Err(Error::rpc("Unable to deserialize account data as RouteData").with_source(e)),
     };
+    if output::is_json() {
+        output::print_json(&instruction::route_data_json(&deserialized));
+        return Ok(())
+    }
     let hash = iroh_blake3::Hash::from_bytes(deserialized.module_cid);
     println!("Route:\t\t\t{:?}", deserialized.route);    
     println!("Module CID:\t\t{:?}", hash.to_string());
     println!("Supported Encodings:\t{:?}", deserialized.encodings);    
     println!("Arguments:");
-    deserialized.arguments.into_iter().for_each(|arg| println!("\t\t\t{}: {:?}", std::str::from_utf8(arg.0.as_slice()).unwrap(), arg.1));
+    deserialized.arguments.into_iter().for_each(|arg| println!("\t\t\t{}: {:?}", String::from_utf8_lossy(arg.0.as_slice()), arg.1));
+    Ok(())
+}
+
+pub fn view_socb(profile: &Profile, seed: &str, display: &SocbDisplayArgs) -> Result<(), Error> {
+    // Raw bytes on stdout would break the single JSON document --output json promises.
+    if output::is_json() && display.format == ContentFormat::Raw && display.out.is_none() {
+        return Err(Error::validation("--format raw cannot be combined with --output json unless the content is saved with --out <PATH>"))
     }
-    if collection.eq("socb"){
-    let deserialized = match rhizo_types::SignedOnchainBytes::deserialize(&mut buffer) {
+    let spinner = Progress::start("Fetching socb config..");
+    let connection = client::establish_connection(profile)?;
+    let keypair = utils::get_keypair(profile)?;
+
+    let seed = format!("socb-{seed}");
+    let (pda_address, _) = client::derive_address(&seed, &keypair.pubkey(), &profile.program_id)?;
+    let account_data = client::get_account_data(&connection, &keypair.pubkey(), &profile.program_id, &seed)?;
+    spinner.finish();
+
+    let deserialized = match rhizo_types::SignedOnchainBytes::deserialize(&mut account_data.as_slice()) {
         Ok(route_data) => route_data, 
-        _ => return Err(Error::new("Unable to deserialize account data as RouteData")),
+        Err(e) => return Err(Error::rpc("Unable to deserialize account data as SignedOnchainBytes").with_source(e)),
+    };
+    if let Some(out) = &display.out {
+        std::fs::write(out, &deserialized.inner)
+            .map_err(|e| Error::io(format!("Unable to write the content to {}", out.display())).with_source(e))?;
+        let note = format!("Wrote {} bytes to {}", deserialized.inner.len(), out.display());
+        // With --format raw stdout carries only the content.
+        match display.format {
+            ContentFormat::Raw => eprintln!("{note}"),
+            _ => output::note(&note),
+        }
+    }
+    if let Some(schema_path) = &display.schema {
+        let value = schema::decode(&schema::load(schema_path)?, &deserialized.inner)?;
+        if output::is_json() {
+            output::print_json(&json!({
+                "pda": pda_address.to_string(),
+                "owner_pubkey": Pubkey::new_from_array(deserialized.owner_pubkey).to_string(),
+                "size": deserialized.inner.len(),
+                "value": value,
+            }));
+            return Ok(())
+        }
+        println!("PDA: \t\t\t{:?}", pda_address);
+        println!("Owner Pubkey: \t\t{:?}", Pubkey::new_from_array(deserialized.owner_pubkey));
+        println!("Size: \t\t\t{} bytes", deserialized.inner.len());
+        println!("Value:");
+        println!("{}", serde_json::to_string_pretty(&value).expect("JSON value serializes"));
+        return Ok(())
+    }
+    if display.format == ContentFormat::Raw && !output::is_json() {
+        return write_raw(&deserialized.inner)
+    }
+    let content = deserialized.inner.as_slice();
+    let shown = match display.full {
+        true => content,
+        false => &content[..content.len().min(VIEW_TRUNCATE_BYTES)],
     };
-    println!("PDA: \t\t\t{:?}", pda_address.unwrap());
+    if output::is_json() {
+        // The raw content went to --out, so only its description is printed.
+        let contents = match display.format {
+            ContentFormat::Raw => serde_json::Value::Null,
+            format => format_content(shown, format).into(),
+        };
+        output::print_json(&json!({
+            "pda": pda_address.to_string(),
+            "owner_pubkey": Pubkey::new_from_array(deserialized.owner_pubkey).to_string(),
+            "contents_hex": hex::encode(shown),
+            "size": content.len(),
+            "format": display.format.to_possible_value().map(|value| value.get_name().to_string()),
+            "contents": contents,
+            "truncated": shown.len() < content.len(),
+        }));
+        return Ok(())
+    }
+    println!("PDA: \t\t\t{:?}", pda_address);
     println!("Owner Pubkey: \t\t{:?}", Pubkey::new_from_array(deserialized.owner_pubkey));
-    println!("Contents: \t\t{:?}", deserialized.inner);
+    println!("Size: \t\t\t{} bytes", content.len());
+    println!("Contents:");
+    println!("{}", format_content(shown, display.format).trim_end_matches('\n'));
+    if shown.len() < content.len() {
+        println!("... {} more bytes ({} total). Pass --full to show all of them or --out <PATH> to save them.", content.len() - shown.len(), content.len());
     }
     Ok(())
 }
 
-pub fn ls(collection: &str) -> Result<(), Error> {
-    let mut spinner = Spinner::new(Spinners::Dots8Bit, format!("Fetching hosted {}s..", collection).into());
-    let connection = client::establish_connection()?;
-    let keypair = utils::get_keypair()?;
+/// Bytes of signed on-chain bytes `view` shows unless `--full` is given.
+const VIEW_TRUNCATE_BYTES: usize = 256;
+
+/// Stream the content of signed on-chain bytes to stdout, e.g. to pipe into other tools.
+pub fn ocb_cat(profile: &Profile, seed: &str) -> Result<(), Error> {
+    let connection = client::establish_connection(profile)?;
+    let keypair = utils::get_keypair(profile)?;
+    let account_data = client::get_account_data(&connection, &keypair.pubkey(), &profile.program_id, &format!("socb-{seed}"))?;
+    let deserialized = rhizo_types::SignedOnchainBytes::deserialize(&mut account_data.as_slice())
+        .map_err(|e| Error::rpc("Unable to deserialize account data as SignedOnchainBytes").with_source(e))?;
+    write_raw(&deserialized.inner)
+}
+
+fn write_raw(content: &[u8]) -> Result<(), Error> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(content).and_then(|_| stdout.flush()) {
+        // The reader, e.g. `head`, stopped early; that is not a failure of ours.
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| Error::io("Unable to write the content to stdout").with_source(e)),
+    }
+}
 
-    let program_pubkey = match Pubkey::from_str(
-        "Ep1SV45cqumZmogwWFy6pVNvMpRerMZUUhSJTbTh2e58"
-    ) {
-        Ok(pubkey) => pubkey,
-        _ => {
-            return Err(Error::new("Unable to create a pubkey for the program address"));
+/// Render `content` for display.
+fn format_content(content: &[u8], format: ContentFormat) -> String {
+    match format {
+        ContentFormat::Hex => hex::encode(content),
+        ContentFormat::Base64 => base64::engine::general_purpose::STANDARD.encode(content),
+        ContentFormat::Utf8 | ContentFormat::Raw => String::from_utf8_lossy(content).into_owned(),
+        ContentFormat::Hexdump => {
+            let mut dump = String::new();
+            for (index, line) in content.chunks(16).enumerate() {
+                let bytes: Vec<String> = line.iter().map(|byte| format!("{byte:02x}")).collect();
+                let text: String = line.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+                dump.push_str(&format!("{:08x}  {:<47}  |{text}|\n", index * 16, bytes.join(" ")));
+            }
+            dump
         }
-    };
+    }
+}
+
+pub fn ls(profile: &Profile, collection: Collection) -> Result<(), Error> {
+    let collection = collection.as_str();
+    let spinner = Progress::start(&format!("Fetching hosted {}s..", collection));
+    let connection = client::establish_connection(profile)?;
+    let keypair = utils::get_keypair(profile)?;
 
     let seed = {
      if collection == "socb" {
         "_dev_socbs"
-     } else if collection == "route" {
-        "_dev_routes"
      } else {
-        panic!("invalid collection, could not list")
+        "_dev_routes"
      }
     };
 
-    let (_, bump_seed) = ProgramPubkey::find_program_address(
-        &[seed.as_bytes(),
-          keypair.pubkey().to_bytes().as_slice()],
-          &program_pubkey    
-    );
-
-    let pda_address = ProgramPubkey::create_program_address(&[seed.as_bytes(), keypair.pubkey().to_bytes().as_slice(), &[bump_seed]], &program_pubkey);
-
-    if pda_address.is_err() {
-        return Err(Error::new("Unable to create a PDA the view command"));
-    }
-
-    let account_data = connection.get_account_data(&pda_address.unwrap());
-    if account_data.is_err(){
-        return Err(Error::new("Unable to fetch account data"))
-    }
-    let account_data = account_data.unwrap();
+    let account_data = client::get_account_data(&connection, &keypair.pubkey(), &profile.program_id, seed)?;
 
     let mut buffer = account_data.as_slice();
     let deserialized = match rhizo_types::DeveloperRoutes::deserialize(&mut buffer) {
         Ok(route_data) => route_data, 
-        _ => return Err(Error::new("Unable to deserialize account data as RouteData")),
+        Err(e) => return Err(Error::rpc(format!("Unable to deserialize account data as a list of {collection}s")).with_source(e)),
     };
 
-    spinner.stop_with_symbol("🗸");
+    spinner.finish();
 
+    if output::is_json() {
+        output::print_json(&deserialized.routes);
+        return Ok(())
+    }
     for r in deserialized.routes {
         println!("{}", r);
     }
@@ -186,45 +249,46 @@ pub fn ls(collection: &str) -> Result<(), Error> {
     Ok(())
 }
 
-pub async fn validate_config(config_path: &str) -> Result<(), Error> {
-    let toml_str = std::fs::read_to_string(config_path);
-    if toml_str.is_err(){
-        return Err(Error::new("Unable to read file at the provided route configuration path as a String"))
-    }
+pub async fn validate_config(config_path: &Path) -> Result<(), Error> {
+    let config = read_route_config(config_path)?;
+    check_route_config(config_path, &config)?;
 
-    let config: Result<Config, _> = toml::from_str(&toml_str.unwrap());
-    if config.is_err(){
-        return Err(Error::new("Unable to read file at provided path as a TOML file"))
+    if output::is_json() {
+        output::print_json(&json!({
+            "valid": true,
+            "route": config.route,
+            "encodings": config.encodings,
+            "arguments": config.arguments.iter().map(|arg| json!({ "name": arg.name, "argument_type": arg.argument_type })).collect::<Vec<_>>(),
+            "cacheable": config.cacheable,
+            "cache_ttl_ms": config.cache_ttl_ms,
+        }));
+        return Ok(())
     }
-    
-    let config = config.unwrap();
-
     println!("Route:\t\t\t{:?}", config.route);
     println!("Supported Encodings:\t{:?}", config.encodings);    
     println!("Arguments:");
     config.arguments
         .into_iter()
         .for_each(
-            |arg| println!("\t\t\t{}: {}", std::str::from_utf8(arg.name.as_bytes()).unwrap(), arg.argument_type)
+            |arg| println!("\t\t\t{}: {}", arg.name, arg.argument_type)
         );
 
     Ok(())
 }
 
-pub async fn validate_module(wasm_path: &str) -> Result<(), Error> {
+pub async fn validate_module(wasm_path: &Path) -> Result<(), Error> {
     let mut store = Store::default();
-    let module = Module::from_file(&store, wasm_path);
-    if module.is_err(){
-        return Err(Error::new("Unable to read file at provided path as a valid WASM module"));
-    }
+    let module = Module::from_file(&store, wasm_path)
+        .map_err(|e| Error::wasm("Unable to read file at provided path as a valid WASM module").with_source(e))?;
     let (mut stdin_tx, stdin_rx) = Pipe::channel();    
     let (stderr_tx, _) = Pipe::channel();
 
     let arg_bytes = Vec::<u8>::new();
     let len_bytes = (arg_bytes.len()as i32).to_le_bytes();
     let concatenated = [len_bytes.to_vec(), arg_bytes].concat();
-    stdin_tx.write_all(concatenated.as_slice()).unwrap();
-    stdin_tx.flush().unwrap();
+    stdin_tx.write_all(concatenated.as_slice())
+        .and_then(|_| stdin_tx.flush())
+        .map_err(|e| Error::io("Unable to write arguments to the module's stdin").with_source(e))?;
 
     let wasi_env_builder = WasiEnvBuilder::new("wasm")
     .capabilities(
@@ -237,61 +301,42 @@ pub async fn validate_module(wasm_path: &str) -> Result<(), Error> {
     .stdin(Box::new(stdin_rx))
     .stdout(Box::new(wasmer_wasix::virtual_fs::host_fs::Stdout::default()));
 
-    let instance = Box::new(wasi_env_builder.instantiate(module.unwrap(), &mut store).unwrap().0);
+    let instance = wasi_env_builder.instantiate(module, &mut store)
+        .map_err(|e| Error::wasm("Unable to instantiate the provided module as a WASIX module").with_source(e))?.0;
     let get_start = instance.exports.get_function("_start");
-    if get_start.is_err() {
-        return Err(Error::new("Unable to find _start function in the provided WASM module"))
+    if let Err(e) = get_start {
+        return Err(Error::wasm("Unable to find _start function in the provided WASM module").with_source(e))
+    }
+    if output::is_json() {
+        output::print_json(&json!({ "valid": true, "path": wasm_path }));
+    } else {
+        println!("WASM file {:?} passed a compile-level module validation", wasm_path);
     }
-    println!("WASM file {:?} passed a compile-level module validation", wasm_path); 
     Ok(()) 
 }
 
-pub async fn deploy(wasm_path: &str, config_path: &str, operation_byte: u8) -> Result<(), Error> {
-    let connection = client::establish_connection().map_err(|e| { 
-        println!("RPC client establish connection failed {:?}", e);
-        Error::new("Unable to establish RPC connections")
-    })?;
-    let keypair = utils::get_keypair()?;
-    let program_pubkey = Pubkey::from_str("Ep1SV45cqumZmogwWFy6pVNvMpRerMZUUhSJTbTh2e58");
+pub async fn deploy(profile: &Profile, wasm_path: &Path, config_path: &Path, operation_byte: u8, force: bool, options: &TransactionOptions) -> Result<(), Error> {
+    let config = read_route_config(config_path)?;
+    check_route_config(config_path, &config)?;
 
-    if program_pubkey.is_err() {        
-        return Err(Error::new("Unable to create pubkey for pre-configured program address"))
-    }
-
-    let toml_str = std::fs::read_to_string(config_path);
-    if toml_str.is_err(){
-        return Err(Error::new("Unable to find a file at the provided route configuration path"))
-    }
-
-    let config: Result<Config, _> = toml::from_str(&toml_str.unwrap());
-    if config.is_err(){
-        return Err(Error::new("Unable to parse route config as TOML"))
-    }
-    let config = config.unwrap();
-    let config = Config {route: format!("route-{}", config.route), encodings: config.encodings, arguments: config.arguments, cacheable: config.cacheable, cache_ttl_ms: config.cache_ttl_ms};
+    let connection = client::establish_connection(profile)?;
+    let ingest = ingest::IngestClient::new(profile)?;
+    let signers = utils::get_signers(profile, options)?;
+    let config = Config {route: format!("{}{}", types::ROUTE_SEED_PREFIX, config.route), encodings: config.encodings, arguments: config.arguments, cacheable: config.cacheable, cache_ttl_ms: config.cache_ttl_ms};
 
     let mut encodings: Vec<rhizo_types::Encoding> = vec![];
     let mut arguments: Vec<(Vec<u8>, rhizo_types::ArgumentType)> = vec![];
 
     for encoding in config.encodings {
-        match encoding.to_lowercase().as_str() {
-            "texthtml" => { encodings.push(TextHtml); }
-            "textplain" => { encodings.push(TextPlain) }
-            "applicationoctetstream" => { encodings.push(ApplicationOctetStream) }
-            "applicationjson" => { encodings.push(ApplicationJson) }
-            _ => {}
-        }
+        encodings.push(utils::parse_encoding(&encoding)?);
     }
 
     for argument in config.arguments {
             arguments.push((argument.name.into_bytes(), utils::parse_argument_type(argument.argument_type)?));
     }
 
-    let wasm_source = std::fs::read(wasm_path);
-    if wasm_source.is_err(){
-        return Err(Error::new("Unable to find content at the WASM source path provided"))
-    }
-    let wasm_source = wasm_source.unwrap();
+    let wasm_source = std::fs::read(wasm_path)
+        .map_err(|e| Error::io(format!("Unable to read the WASM module at {}", wasm_path.display())).with_source(e))?;
 
     let mut hasher = iroh_blake3::Hasher::new();
     hasher.update(wasm_source.as_slice());
@@ -314,70 +359,883 @@ pub async fn deploy(wasm_path: &str, config_path: &str, operation_byte: u8) -> R
         metadata: route_data.clone(),
         source: wasm_source,
     };
+    let payload = ingest::Payload::new(&route_source, signers.caller.as_ref())?;
+    output::note(&format!("Module size:\t\t{} bytes raw, {} bytes gzipped (limit {})", payload.module_size, payload.gzipped.len(), profile.max_module_size));
+    payload.check_size(profile.max_module_size)?;
     
-    let _ = client::update_route_data(&keypair, &program_pubkey.unwrap(), &connection, &route_data, operation_byte)?;
+    let deployed = match force {
+        true => None,
+        false => client::find_account_data(&connection, &signers.caller.pubkey(), &profile.program_id, &route_data.route)?
+            .map(|data| rhizo_types::RouteData::deserialize(&mut data.as_slice()))
+            .transpose()
+            .map_err(|e| Error::rpc("Unable to deserialize account data as RouteData").with_source(e))?,
+    };
+    let mut skip_present = false;
+    if let Some(deployed) = &deployed {
+        let changed = changed_route_fields(deployed, &route_data);
+        if changed.is_empty() {
+            if output::is_json() {
+                let mut document = instruction::route_data_json(&route_data);
+                document["unchanged"] = json!(true);
+                output::print_json(&document);
+            } else {
+                println!("Route {:?} is already deployed with module {} and the same config; nothing to do. Pass --force to redeploy.", route_data.route, hash);
+            }
+            return Ok(())
+        }
+        output::note(&format!("Changed since the last deploy: {}", changed.join(", ")));
+        // Endpoints that already hold an unchanged module only need it read back.
+        skip_present = !changed.contains(&"module_cid");
+    }
+
+    let prepared = client::update_route_data(&signers, &profile.program_id, &connection, options, &route_data, operation_byte)?;
+    if let client::Prepared::DryRun(_) = prepared {
+        output::note("Dry run: the module was not uploaded to the ingest service.");
+        print_outcome(profile, &prepared.send()?);
+        return Ok(())
+    }
 
     if operation_byte.eq(&1u8) {
+        report(profile, "deploy", Some(&route_data.route), &prepared.send()?);
+        return Ok(())
+    }
+
+    // Upload before committing the route so on-chain state never references a module an ingest
+    // endpoint does not have. If the transaction fails afterwards, the upload is harmless.
+    if payload.signature.is_none() {
+        eprintln!("[WARN] Only the pubkey of {} is available, so the module is uploaded without a signature. Endpoints that require signed uploads will reject it.", signers.caller.pubkey());
+    }
+    let spinner = Progress::start(&format!("Pushing module & route config to {} ingest endpoint(s)", profile.ingest_urls.len()));
+    let replicas = ingest.replicate(&profile.ingest_urls, &payload, &route_data.module_cid, skip_present).await;
+    spinner.finish();
+    if !output::is_json() {
+        print_replicas(&replicas);
+    }
+    check_replicas(&replicas, "the route was not updated")?;
+
+    let outcome = prepared.send()?;
+    let Outcome::Sent(confirmed) = outcome else {
+        print_outcome(profile, &outcome);
         return Ok(())
+    };
+    record(profile, "deploy", Some(&route_data.route), Some(hash.to_string()), &confirmed);
+
+    if output::is_json() {
+        let mut document = instruction::route_data_json(&route_data);
+        document["signature"] = json!(confirmed.signature.to_string());
+        document["slot"] = json!(confirmed.slot);
+        document["explorer_url"] = json!(profile.explorer_url(&confirmed.signature.to_string()));
+        document["upload"] = json!({
+            "module_size": payload.module_size,
+            "raw_size": payload.raw_size,
+            "gzipped_size": payload.gzipped.len(),
+            "uploaded": replicas.iter().any(|replica| matches!(replica.upload, Ok(ingest::Upload::Uploaded))),
+            "endpoints": replicas.iter().map(replica_json).collect::<Vec<_>>(),
+        });
+        output::print_json(&document);
+        return Ok(())
+    }
+    println!("-------------------------------");        
+    print_confirmed(profile, &confirmed);
+    println!("Route:\t\t\t{:?}", route_data.route);
+    println!("Module CID:\t\t{:?}", hash.to_string());
+    println!("Supported Encodings:\t{:?}", route_data.encodings);    
+    println!("Arguments:");
+    route_data.arguments
+        .into_iter()
+        .for_each(
+            |arg| println!("\t\t\t{}: {:?}", String::from_utf8_lossy(arg.0.as_slice()), arg.1)
+        );
+    Ok(())
+}
+
+/// Per-endpoint upload and read-back results, one tab-separated row per ingest endpoint.
+fn print_replicas(replicas: &[ingest::Replica]) {
+    println!("Endpoint\tUpload\tRead-back");
+    for replica in replicas {
+        let upload = match &replica.upload {
+            Ok(ingest::Upload::Uploaded) => "uploaded",
+            Ok(ingest::Upload::AlreadyPresent) => "already present",
+            Err(_) => "failed",
+        };
+        let read_back = match &replica.read_back {
+            Some(Ok(ingest::ReadBack::Verified)) => "verified",
+            Some(Ok(ingest::ReadBack::Unsupported)) => "unsupported",
+            Some(Err(_)) => "failed",
+            None => "-",
+        };
+        println!("{}\t{upload}\t{read_back}", replica.ingest_url);
+    }
+}
+
+fn replica_json(replica: &ingest::Replica) -> serde_json::Value {
+    json!({
+        "ingest_url": replica.ingest_url,
+        "upload": match &replica.upload {
+            Ok(ingest::Upload::Uploaded) => "uploaded",
+            Ok(ingest::Upload::AlreadyPresent) => "already_present",
+            Err(_) => "failed",
+        },
+        "read_back": match &replica.read_back {
+            Some(Ok(ingest::ReadBack::Verified)) => Some("verified"),
+            Some(Ok(ingest::ReadBack::Unsupported)) => Some("unsupported"),
+            Some(Err(_)) => Some("failed"),
+            None => None,
+        },
+        "error": replica.error().map(|e| e.message()),
+    })
+}
+
+/// Fail with every endpoint's error if any endpoint failed. The error has the first failure's kind
+/// and status so the hint matches it.
+fn check_replicas(replicas: &[ingest::Replica], consequence: &str) -> Result<(), Error> {
+    let failures: Vec<_> = replicas.iter()
+        .filter_map(|replica| replica.error().map(|e| (replica, e)))
+        .collect();
+    let Some((_, first)) = failures.first() else { return Ok(()) };
+    let mut message = format!("{} of {} ingest endpoints failed; {consequence}:", failures.len(), replicas.len());
+    for (replica, e) in &failures {
+        message.push_str(&format!("\n  {}: {}", replica.ingest_url, e.message()));
+    }
+    match first {
+        Error::Tls { .. } => Err(Error::tls(message)),
+        Error::Ingest { status, .. } => Err(Error::ingest(message, *status)),
+        _ => Err(Error::ingest(message, None)),
+    }
+}
+
+/// Names of the route data fields that differ between the deployed route and the one being deployed.
+fn changed_route_fields(deployed: &rhizo_types::RouteData, route_data: &rhizo_types::RouteData) -> Vec<&'static str> {
+    let mut changed = vec![];
+    if deployed.module_cid != route_data.module_cid {
+        changed.push("module_cid");
+    }
+    if deployed.encodings != route_data.encodings {
+        changed.push("encodings");
+    }
+    if deployed.arguments != route_data.arguments {
+        changed.push("arguments");
+    }
+    if deployed.cache_config != route_data.cache_config {
+        changed.push("cache_config");
+    }
+    changed
+}
+
+/// Re-upload the modules of deployed routes whose CID an ingest endpoint does not have, e.g. after
+/// a deploy made by an older rhizo-cli failed to upload. Modules are matched to routes by CID.
+pub async fn repair(profile: &Profile, wasm_paths: &[PathBuf]) -> Result<(), Error> {
+    let mut modules = HashMap::new();
+    for path in wasm_paths {
+        let source = std::fs::read(path)
+            .map_err(|e| Error::io(format!("Unable to read the WASM module at {}", path.display())).with_source(e))?;
+        modules.insert(*iroh_blake3::hash(&source).as_bytes(), (path, source));
     }
 
-    let mut spinner = Spinner::new(Spinners::Dots8Bit, "Pushing module & route config to devnet".into());
+    let spinner = Progress::start("Fetching hosted routes..");
+    let connection = client::establish_connection(profile)?;
+    let ingest = ingest::IngestClient::new(profile)?;
+    let keypair = utils::get_keypair(profile)?;
+    let account_data = client::get_account_data(&connection, &keypair.pubkey(), &profile.program_id, "_dev_routes")?;
+    let routes = rhizo_types::DeveloperRoutes::try_from_slice(&account_data)
+        .map_err(|e| Error::rpc("Unable to deserialize account data as a list of routes").with_source(e))?
+        .routes;
+    spinner.finish();
 
-    let client = Client::new();
-    let request = Request::builder()
-        .method("POST")
-        .uri("http://euro.rhizo.dev/ingest")
-        .header(CONTENT_TYPE, "application/json")
-        .body(Body::from(route_source.try_to_vec().expect("route data serializes")))
-        .unwrap();
+    let mut report = vec![];
+    let mut unresolved = vec![];
+    for route in routes {
+        let account_data = client::get_account_data(&connection, &keypair.pubkey(), &profile.program_id, &route)?;
+        let route_data = rhizo_types::RouteData::deserialize(&mut account_data.as_slice())
+            .map_err(|e| Error::rpc(format!("Unable to deserialize account data for {route:?} as RouteData")).with_source(e))?;
+        let cid = iroh_blake3::Hash::from_bytes(route_data.module_cid).to_hex().to_string();
 
-    let response = client.request(request).await.unwrap();
-   
-    if response.status().eq(&StatusCode::OK){
-        let body_bytes = hyper::body::to_bytes(response.into_body()).await;
-        if body_bytes.is_err(){
-            return Err(Error::new("Unable to read response bytes from Rhizo server"))
+        let mut missing = vec![];
+        for ingest_url in &profile.ingest_urls {
+            // An endpoint that cannot be asked is treated as missing the module, so the upload
+            // below reports why it failed.
+            if !ingest.has_module(ingest_url, &route_data.module_cid).await.unwrap_or(false) {
+                missing.push(ingest_url.clone());
+            }
         }
-        let body_bytes = body_bytes.unwrap();
-        let body_str = std::str::from_utf8(&body_bytes);
-        if body_str.is_err(){
-            return Err(Error::new("Unable to parse respoonse body as UTF-8"))
+
+        let status = if missing.is_empty() {
+            "present".to_string()
+        } else if let Some((path, source)) = modules.get(&route_data.module_cid) {
+            let payload = ingest::Payload::new(&rhizo_types::RouteDeploy { metadata: route_data.clone(), source: source.clone() }, &keypair)?;
+            let replicas = ingest.replicate(&missing, &payload, &route_data.module_cid, false).await;
+            if let Err(e) = check_replicas(&replicas, "the module is still missing there") {
+                let status = e.message().replace('\n', " ");
+                unresolved.push(format!("{route}: {status}"));
+                status
+            } else {
+                format!("re-uploaded {} to {}", path.display(), missing.join(", "))
+            }
+        } else {
+            let status = format!("missing from {}, no matching local module", missing.join(", "));
+            unresolved.push(format!("{route}: {status}"));
+            status
+        };
+        report.push((route, cid, status));
+    }
+
+    if !output::is_json() {
+        for (route, cid, status) in &report {
+            println!("{route}\t{cid}\t{status}");
         }
-        spinner.stop_with_symbol("🗸");
-        println!("-------------------------------");        
-        println!("Route:\t\t\t{:?}", route_data.route);
-        println!("Module CID:\t\t{:?}", hash.to_string());
-        println!("Supported Encodings:\t{:?}", route_data.encodings);    
-        println!("Arguments:");
-        route_data.arguments
-            .into_iter()
-            .for_each(
-                |arg| println!("\t\t\t{}: {:?}", std::str::from_utf8(arg.0.as_slice()).unwrap(), arg.1)
-            );
-    } else {
-        if response.status().as_str().eq("413"){
-            return Err(Error::new("Payload too large. WASM module must gzip to less than 2mb."))
+    }
+    if !unresolved.is_empty() {
+        // With --output json the error is the run's only document, so it names the unresolved
+        // routes itself.
+        return Err(Error::validation(format!("{} route(s) reference modules an ingest endpoint does not have. Pass the matching .wasm files or redeploy those routes:\n  {}", unresolved.len(), unresolved.join("\n  "))))
+    }
+    if output::is_json() {
+        output::print_json(&json!({
+            "routes": report.iter().map(|(route, cid, status)| json!({ "route": route, "module_cid": cid, "status": status })).collect::<Vec<_>>(),
+        }));
+    }
+    Ok(())
+}
+
+pub fn ocb_alloc(profile: &Profile, seed: &str, size: usize, options: &TransactionOptions) -> Result<(), Error> {
+    let signers = utils::get_signers(profile, options)?;
+    let connection = client::establish_connection(profile)?;
+    let outcome = client::alloc_ocb(&signers, &profile.program_id, &connection, options, &rhizo_types::SignedOnchainBytesUpdate { seed: seed.to_string(), bytes: rhizo_types::SignedOnchainBytes { inner: vec![0u8; size], owner_pubkey: signers.caller.pubkey().to_bytes()}, bump_seed: None, })?;
+    report(profile, "socb alloc", Some(seed), &outcome);
+    Ok(())
+}
+
+pub fn ocb_resize(profile: &Profile, seed: &str, size: usize, force: bool, options: &TransactionOptions) -> Result<(), Error> {
+    let signers = utils::get_signers(profile, options)?;
+    let connection = client::establish_connection(profile)?;
+    let outcome = client::resize_ocb(&signers, &profile.program_id, &connection, options, seed, size, force)?;
+    report(profile, "socb resize", Some(seed), &outcome);
+    Ok(())
+}
+
+pub fn ocb_write(profile: &Profile, seed: &str, content: &SocbContentArgs, schema_path: Option<&Path>, options: &TransactionOptions) -> Result<(), Error> {
+    let content = read_socb_content(content, schema_path)?;
+    let hash = iroh_blake3::hash(&content);
+    let signers = utils::get_signers(profile, options)?;
+    let connection = client::establish_connection(profile)?;
+    let outcome = client::write_ocb(&signers, &profile.program_id, &connection, options, &rhizo_types::SignedOnchainBytesUpdate { seed: seed.to_string() , bytes: rhizo_types::SignedOnchainBytes { inner: content, owner_pubkey: signers.caller.pubkey().to_bytes() }, bump_seed: None, })?;
+    let Outcome::Sent(confirmed) = &outcome else {
+        print_outcome(profile, &outcome);
+        return Ok(())
+    };
+    record(profile, "socb write", Some(seed), None, confirmed);
+
+    // Read the content back so a write that landed differently than intended is not reported as done.
+    let account_data = client::get_account_data(&connection, &signers.caller.pubkey(), &profile.program_id, &format!("socb-{seed}"))?;
+    let written = rhizo_types::SignedOnchainBytes::deserialize(&mut account_data.as_slice())
+        .map_err(|e| Error::rpc("Unable to deserialize account data as SignedOnchainBytes").with_source(e))?;
+    let on_chain = iroh_blake3::hash(&written.inner);
+    if on_chain != hash {
+        return Err(Error::validation(format!("Transaction {} landed, but the on-chain content of {seed:?} has BLAKE3 {on_chain} ({} bytes), not {hash} as written. Run the write again.", confirmed.signature, written.inner.len())))
+    }
+
+    if output::is_json() {
+        output::print_json(&json!({
+            "signature": confirmed.signature.to_string(),
+            "slot": confirmed.slot,
+            "explorer_url": profile.explorer_url(&confirmed.signature.to_string()),
+            "size": written.inner.len(),
+            "blake3": hash.to_string(),
+            "verified": true,
+        }));
+        return Ok(())
+    }
+    print_confirmed(profile, confirmed);
+    println!("Size:\t\t\t{} bytes", written.inner.len());
+    println!("BLAKE3:\t\t\t{hash} (verified on-chain)");
+    Ok(())
+}
+
+/// Content for `socb write` from a file, stdin, a hex or base64 literal, or JSON encoded with a schema.
+fn read_socb_content(args: &SocbContentArgs, schema_path: Option<&Path>) -> Result<Vec<u8>, Error> {
+    if let Some(hex) = &args.hex {
+        return hex::decode(hex.trim()).map_err(|e| Error::validation("--hex is not valid hex").with_source(e))
+    }
+    if let Some(encoded) = &args.base64 {
+        return base64::engine::general_purpose::STANDARD.decode(encoded.trim())
+            .map_err(|e| Error::validation("--base64 is not valid base64").with_source(e))
+    }
+    if let (Some(path), Some(schema_path)) = (&args.json, schema_path) {
+        let layout = schema::load(schema_path)?;
+        let value: serde_json::Value = serde_json::from_slice(&read_input(path)?)
+            .map_err(|e| Error::validation(format!("{} is not valid JSON", path.display())).with_source(e))?;
+        return schema::encode(&layout, &value)
+    }
+    match args.content_path.as_deref() {
+        Some(path) => read_input(path),
+        None => unreachable!("clap requires one content source"),
+    }
+}
+
+/// Read `path`, or stdin for `-`.
+fn read_input(path: &Path) -> Result<Vec<u8>, Error> {
+    if path == Path::new("-") {
+        let mut content = vec![];
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut content)
+            .map_err(|e| Error::io("Unable to read the content from stdin").with_source(e))?;
+        return Ok(content)
+    }
+    std::fs::read(path)
+        .map_err(|e| Error::io(format!("Unable to read the content at {}", path.display())).with_source(e))
+}
+
+pub fn nonce_create(profile: &Profile, seed: &str, lamports: Option<u64>, options: &TransactionOptions) -> Result<(), Error> {
+    let signers = utils::get_signers(profile, options)?;
+    let connection = client::establish_connection(profile)?;
+    let address = client::nonce_address(&signers.caller.pubkey(), seed)?;
+    let outcome = client::create_nonce_account(&signers, &connection, options, seed, lamports)?;
+    if let Outcome::Sent(confirmed) = &outcome {
+        record(profile, "nonce create", Some(seed), None, confirmed);
+        if output::is_json() {
+            output::print_json(&json!({
+                "signature": confirmed.signature.to_string(),
+                "slot": confirmed.slot,
+                "explorer_url": profile.explorer_url(&confirmed.signature.to_string()),
+                "address": address.to_string(),
+            }));
+        } else {
+            print_confirmed(profile, confirmed);
+            println!("Nonce Account:\t\t{address}");
+            println!("Use it with --durable-nonce {address}.");
+        }
+        return Ok(())
+    }
+    print_outcome(profile, &outcome);
+    Ok(())
+}
+
+pub fn nonce_show(profile: &Profile, account: &NonceAccountArgs) -> Result<(), Error> {
+    let address = match account.address {
+        Some(address) => address,
+        None => client::nonce_address(&utils::get_keypair(profile)?.pubkey(), &account.seed)?,
+    };
+    let connection = client::establish_connection(profile)?;
+    let nonce = client::get_nonce(&connection, &address)?;
+
+    if output::is_json() {
+        output::print_json(&json!({
+            "address": address.to_string(),
+            "authority": nonce.data.authority.to_string(),
+            "blockhash": nonce.data.blockhash().to_string(),
+            "lamports_per_signature": nonce.data.fee_calculator.lamports_per_signature,
+            "lamports": nonce.lamports,
+        }));
+        return Ok(())
+    }
+    println!("Nonce Account:\t\t{address}");
+    println!("Authority:\t\t{}", nonce.data.authority);
+    println!("Blockhash:\t\t{}", nonce.data.blockhash());
+    println!("Fee:\t\t\t{} lamports per signature", nonce.data.fee_calculator.lamports_per_signature);
+    println!("Balance:\t\t{}", output::format_lamports(nonce.lamports));
+    Ok(())
+}
+
+pub fn nonce_withdraw(profile: &Profile, account: &NonceAccountArgs, lamports: u64, to: Option<Pubkey>, options: &TransactionOptions) -> Result<(), Error> {
+    let signers = utils::get_signers(profile, options)?;
+    let address = match account.address {
+        Some(address) => address,
+        None => client::nonce_address(&signers.caller.pubkey(), &account.seed)?,
+    };
+    let connection = client::establish_connection(profile)?;
+    let to = to.unwrap_or_else(|| signers.caller.pubkey());
+    let outcome = client::withdraw_nonce_account(&signers, &connection, options, &address, &to, lamports)?;
+    report(profile, "nonce withdraw", Some(&address.to_string()), &outcome);
+    Ok(())
+}
+
+/// Add the profile keypair's signature to an exported transaction after showing what it does.
+pub fn sign(profile: &Profile, path: &Path, out: Option<&Path>, options: &TransactionOptions) -> Result<(), Error> {
+    let keypair = utils::get_keypair(profile)?;
+    let mut transaction = offline::ExportedTransaction::read(path)?;
+    let message = transaction.message()?;
+
+    // The review goes to stderr with the prompt, so a transaction written to stdout stays clean.
+    if !output::is_json() {
+        let mut review = std::io::stderr();
+        let _ = writeln!(review, "Action:\t\t\t{}", transaction.action);
+        let _ = writeln!(review, "Fee Payer:\t\t{}", message.account_keys[0]);
+        let _ = writeln!(review, "Recent Blockhash:\t{}", message.recent_blockhash);
+        let _ = writeln!(review, "Fee:\t\t\t{} (estimated at export)", output::format_lamports(transaction.fee_lamports));
+        let _ = writeln!(review, "Rent:\t\t\t{} (estimated at export)", output::format_lamports(transaction.rent_lamports));
+        let instructions: Vec<_> = instruction::decompile(&message).iter().map(instruction::describe).collect();
+        let _ = print_instructions(&mut review, &instructions);
+    }
+    let prompt = format!("  Sign this {} as {}?", transaction.action, keypair.pubkey());
+    client::confirm_transaction(options, transaction.fee_lamports, transaction.rent_lamports, prompt)?;
+    transaction.sign(&keypair)?;
+
+    let out = out.unwrap_or(path);
+    transaction.write(out)?;
+    let missing = transaction.missing_signers();
+    if out == Path::new("-") {
+        if !output::is_json() && !missing.is_empty() {
+            eprintln!("Still missing signatures from {}.", missing.join(", "));
+        }
+        return Ok(())
+    }
+    if output::is_json() {
+        output::print_json(&json!({
+            "signed": out,
+            "signer": keypair.pubkey().to_string(),
+            "missing_signatures": missing,
+        }));
+        return Ok(())
+    }
+    println!("Signed:\t\t\t{}", out.display());
+    println!("Signer:\t\t\t{}", keypair.pubkey());
+    match missing.is_empty() {
+        true => println!("Broadcast it with `rhizo-cli submit {}`.", out.display()),
+        false => println!("Still missing signatures from {}.", missing.join(", ")),
+    }
+    Ok(())
+}
+
+/// Broadcast a fully signed exported transaction.
+pub fn submit(profile: &Profile, path: &Path) -> Result<(), Error> {
+    let exported = offline::ExportedTransaction::read(path)?;
+    let transaction = exported.transaction()?;
+    let connection = client::establish_connection(profile)?;
+    let confirmed = client::submit(&connection, &transaction, exported.fee_lamports)?;
+    record(profile, "submit", None, None, &confirmed);
+    print_confirmed(profile, &confirmed);
+    Ok(())
+}
+
+/// List recent transactions from the local journal or, without `local`, those the cluster has
+/// for the current keypair.
+pub fn history(profile: &Profile, local: bool, key: Option<&str>, limit: usize) -> Result<(), Error> {
+    if local {
+        let entries = journal::read(&profile.journal_path)?;
+        let entries: Vec<_> = entries.iter()
+            .rev()
+            .filter(|entry| key.is_none() || entry.key.as_deref() == key)
+            .take(limit)
+            .collect();
+        if output::is_json() {
+            output::print_json(&json!({ "journal": profile.journal_path, "entries": entries }));
+            return Ok(())
+        }
+        if entries.is_empty() {
+            println!("No transactions recorded in {}.", profile.journal_path.display());
+            return Ok(())
+        }
+        println!("Time (UTC)\tProfile\tCommand\tKey\tModule CID\tFee\tSignature");
+        for entry in entries {
+            println!("{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                journal::format_time(entry.timestamp),
+                entry.profile,
+                entry.command,
+                entry.key.as_deref().unwrap_or("-"),
+                entry.module_cid.as_deref().unwrap_or("-"),
+                entry.fee_lamports,
+                entry.signature);
+        }
+        return Ok(())
+    }
+
+    let address = utils::get_keypair(profile)?.pubkey();
+    let connection = client::establish_connection(profile)?;
+    let config = || solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config { limit: Some(limit), ..Default::default() };
+    let signatures = connection.with_retry("Fetching recent transactions", |rpc| rpc.get_signatures_for_address_with_config(&address, config()))
+        .map_err(|e| Error::rpc(format!("Unable to fetch recent transactions of {address}")).with_source(e))?;
+    if output::is_json() {
+        let entries: Vec<_> = signatures.iter().map(|status| json!({
+            "signature": status.signature,
+            "slot": status.slot,
+            "block_time": status.block_time,
+            "failed": status.err.is_some(),
+            "explorer_url": profile.explorer_url(&status.signature),
+        })).collect();
+        output::print_json(&json!({ "address": address.to_string(), "entries": entries }));
+        return Ok(())
+    }
+    if signatures.is_empty() {
+        println!("No transactions found for {address}.");
+        return Ok(())
+    }
+    println!("Time (UTC)\tSlot\tStatus\tSignature");
+    for status in &signatures {
+        let time = status.block_time.map(|time| journal::format_time(time.max(0) as u64)).unwrap_or_else(|| "-".to_string());
+        let result = if status.err.is_some() { "failed" } else { "ok" };
+        println!("{time}\t{}\t{result}\t{}", status.slot, status.signature);
+    }
+    Ok(())
+}
+
+pub fn explain_error(code: Option<&str>) -> Result<(), Error> {
+    let entries: Vec<&program_error::ProgramErrorCode> = match code {
+        Some(code) => {
+            let code = program_error::parse_code(code)?;
+            let entry = program_error::lookup(code)
+                .ok_or_else(|| Error::validation(format!("unknown rhizo error {code}")))?;
+            vec![entry]
+        }
+        None => program_error::PROGRAM_ERRORS.iter().collect(),
+    };
+
+    if output::is_json() {
+        if code.is_some() {
+            output::print_json(entries[0]);
         } else {
-            return Err(Error::new(&format!("Error from rhizo server {:?}", response.status().as_str())))
+            output::print_json(&entries);
+        }
+        return Ok(())
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("Code:\t\t\t{} (0x{:x})", entry.code, entry.code);
+        println!("Name:\t\t\t{}", entry.name);
+        println!("Description:\t\t{}", entry.description);
+        println!("Remediation:\t\t{}", entry.remediation);
+    }
+    Ok(())
+}
+
+/// Check an ingest upload's signature from its headers, given as a file and/or flags. Fails with
+/// a validation error if the signature, the module or the signature's age does not check out.
+pub fn verify_upload(headers_path: Option<&Path>, fields: &UploadHeaderArgs, module_path: Option<&Path>, max_age: Option<u64>) -> Result<(), Error> {
+    let mut headers = HashMap::new();
+    if let Some(path) = headers_path {
+        let contents = match path.to_str() {
+            Some("-") => std::io::read_to_string(std::io::stdin())
+                .map_err(|e| Error::io("Unable to read headers from stdin").with_source(e))?,
+            _ => std::fs::read_to_string(path)
+                .map_err(|e| Error::io(format!("Unable to read headers from {}", path.display())).with_source(e))?,
+        };
+        // Lines without a colon, like the request line of a dump, are skipped.
+        for (name, value) in contents.lines().filter_map(|line| line.split_once(':')) {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let flags = [
+        (ingest::PUBKEY_HEADER, &fields.pubkey),
+        (ingest::SIGNATURE_HEADER, &fields.signature),
+        (ingest::ROUTE_HEADER, &fields.route),
+        (ingest::MODULE_CID_HEADER, &fields.module_cid),
+        (ingest::TIMESTAMP_HEADER, &fields.timestamp),
+        (ingest::NONCE_HEADER, &fields.nonce),
+    ];
+    for (name, value) in flags {
+        if let Some(value) = value {
+            headers.insert(name.to_string(), value.clone());
+        }
+    }
+    let upload = ingest::UploadSignature::from_headers(|name| headers.get(name).map(String::as_str))
+        .map_err(|e| Error::validation(format!("{}. Pass --headers or the matching flag, e.g. --pubkey for x-rhizo-pubkey.", e.message())))?;
+    let module_cid = iroh_blake3::Hash::from_bytes(upload.module_cid);
+
+    if !upload.verify() {
+        return Err(Error::validation(format!("The signature is not a valid signature by {} of route {:?} with module {module_cid}", upload.pubkey, upload.route)))
+    }
+    if let Some(path) = module_path {
+        let source = std::fs::read(path)
+            .map_err(|e| Error::io(format!("Unable to read the WASM module at {}", path.display())).with_source(e))?;
+        let hash = iroh_blake3::hash(&source);
+        if hash != module_cid {
+            return Err(Error::validation(format!("{} hashes to {hash}, not the signed module {module_cid}", path.display())))
+        }
+    }
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default();
+    let age = now.saturating_sub(upload.timestamp);
+    if let Some(max_age) = max_age {
+        if age > max_age {
+            return Err(Error::validation(format!("The upload was signed {age}s ago, longer than --max-age {max_age}s")))
+        }
+    }
+
+    if output::is_json() {
+        output::print_json(&json!({
+            "valid": true,
+            "pubkey": upload.pubkey.to_string(),
+            "route": upload.route,
+            "module_cid": module_cid.to_hex().to_string(),
+            "timestamp": upload.timestamp,
+            "age_secs": age,
+            "nonce": hex::encode(upload.nonce),
+            "module_checked": module_path.is_some(),
+        }));
+        return Ok(())
+    }
+    println!("Signature:\t\tvalid");
+    println!("Signer:\t\t\t{}", upload.pubkey);
+    println!("Route:\t\t\t{:?}", upload.route);
+    println!("Module CID:\t\t{module_cid}{}", if module_path.is_some() { " (matches the module)" } else { "" });
+    println!("Signed At:\t\t{} ({age}s ago)", upload.timestamp);
+    println!("Nonce:\t\t\t{}", hex::encode(upload.nonce));
+    Ok(())
+}
+
+pub fn config_show(overrides: &ProfileOverrides) -> Result<(), Error> {
+    let path = profile::config_path(overrides)?;
+    let profile = profile::load(overrides)?;
+
+    if output::is_json() {
+        output::print_json(&json!({
+            "config_file": path,
+            "profile": profile.name,
+            "rpc_url": profile.rpc_url,
+            "ws_url": profile.ws_url,
+            "program_id": profile.program_id.to_string(),
+            "ingest_urls": profile.ingest_urls,
+            "keypair": profile.keypair_path,
+            "commitment": profile.commitment.commitment.to_string(),
+            "max_module_size": profile.max_module_size,
+            "attempts": profile.retry.attempts,
+            "timeout_secs": profile.retry.timeout.as_secs(),
+            "ca_file": profile.ca_file,
+            "spki_pins": profile.spki_pins,
+        }));
+        return Ok(())
+    }
+    println!("Config File:\t\t{}", path.display());
+    println!("Profile:\t\t{}", profile.name);
+    println!("RPC URL:\t\t{}", profile.rpc_url);
+    println!("WebSocket URL:\t\t{}", profile.ws_url);
+    println!("Program ID:\t\t{}", profile.program_id);
+    println!("Ingest URLs:\t\t{}", profile.ingest_urls.join(", "));
+    println!("Keypair Path:\t\t{}", profile.keypair_path.display());
+    println!("Commitment:\t\t{}", profile.commitment.commitment);
+    println!("Max Module Size:\t{} bytes", profile.max_module_size);
+    println!("Attempts:\t\t{}", profile.retry.attempts);
+    println!("Timeout:\t\t{}s", profile.retry.timeout.as_secs());
+    println!("CA File:\t\t{}", profile.ca_file.as_ref().map(|path| path.display().to_string()).unwrap_or_else(|| "none".to_string()));
+    for (host, pins) in &profile.spki_pins {
+        println!("SPKI Pins:\t\t{host}: {}", pins.join(", "));
+    }
+    Ok(())
+}
+
+pub fn config_set(overrides: &ProfileOverrides, key: &str, value: &str) -> Result<(), Error> {
+    match key {
+        "program_id" => {
+            Pubkey::from_str(value)
+                .map_err(|e| Error::validation(format!("{value:?} is not a valid program id")).with_source(e))?;
         }
-        //return Err(Error::new(format!("Error from Rhizo server {:?}", response.status()).as_str()))
+        "commitment" => {
+            profile::parse_commitment(value).map_err(|e| Error::validation(e.message()))?;
+        }
+        "rpc_url" | "ws_url" | "ingest_url" => {
+            url::Url::parse(value)
+                .map_err(|e| Error::validation(format!("{value:?} is not a valid URL")).with_source(e))?;
+        }
+        "ingest_urls" => {
+            let urls = profile::split_list(value);
+            if urls.is_empty() {
+                return Err(Error::validation("ingest_urls needs at least one URL"))
+            }
+            for url in urls {
+                url::Url::parse(&url)
+                    .map_err(|e| Error::validation(format!("{url:?} is not a valid URL")).with_source(e))?;
+            }
+        }
+        _ => {}
+    }
+
+    let path = profile::config_path(overrides)?;
+    let mut config = profile::read_config(&path)?;
+    let name = profile::active_name(overrides, &config);
+    config.profiles.entry(name.clone()).or_default().set(key, value.to_string())?;
+    profile::write_config(&path, &config)?;
+
+    if output::is_json() {
+        output::print_json(&json!({ "profile": name, "key": key, "value": value }));
+    } else {
+        println!("Set {key} = {value:?} in profile {name:?}");
+    }
+    Ok(())
+}
+
+pub fn config_use(overrides: &ProfileOverrides, name: &str) -> Result<(), Error> {
+    let path = profile::config_path(overrides)?;
+    let mut config = profile::read_config(&path)?;
+    if !config.profiles.contains_key(name) && !profile::is_builtin(name) {
+        return Err(Error::validation(format!("Profile {name:?} is not defined in {}. Create it with `rhizo-cli --profile {name} config set rpc_url <url>`.", path.display())))
+    }
+    config.active_profile = Some(name.to_string());
+    profile::write_config(&path, &config)?;
+
+    if output::is_json() {
+        output::print_json(&json!({ "active_profile": name }));
+    } else {
+        println!("Switched to profile {name:?}");
     }
     Ok(())
 }
 
-pub fn ocb_alloc(seed: &str, size: usize) -> Result<(), Error> {
-    let keypair = utils::get_keypair()?;
-    let program_pubkey = Pubkey::from_str("Ep1SV45cqumZmogwWFy6pVNvMpRerMZUUhSJTbTh2e58")
-        .map_err(|_| Error::new("Unable to create program pubkey"))?; 
-    let connection = client::establish_connection().map_err(|_| Error::new("Unable to establish RPC connections"))?; 
-    client::alloc_ocb(&keypair, &program_pubkey, &connection, &rhizo_types::SignedOnchainBytesUpdate { seed: seed.to_string(), bytes: rhizo_types::SignedOnchainBytes { inner: vec![0u8; size], owner_pubkey: keypair.pubkey().to_bytes()}, bump_seed: None, })    
+/// Read and parse a route config, reporting TOML errors by line and column.
+fn read_route_config(config_path: &Path) -> Result<Config, Error> {
+    let toml_str = std::fs::read_to_string(config_path)
+        .map_err(|e| Error::config(format!("Unable to read the route config at {}", config_path.display())).with_source(e))?;
+    toml::from_str(&toml_str)
+        .map_err(|e| Error::config(format!("Unable to parse the route config at {}", config_path.display())).with_source(TomlError::new(&toml_str, &e)))
+}
+
+/// Fail with every rule violation in the route config, one per line.
+fn check_route_config(config_path: &Path, config: &Config) -> Result<(), Error> {
+    let violations = config.violations();
+    if violations.is_empty() {
+        return Ok(())
+    }
+    let mut message = format!("Route config at {} has {} problem(s):", config_path.display(), violations.len());
+    for violation in &violations {
+        message.push_str(&format!("\n  {violation}"));
+    }
+    Err(Error::validation(message))
+}
+
+/// Append a sent transaction to the local journal. The transaction has already landed, so a
+/// journal that cannot be written only warns.
+fn record(profile: &Profile, command: &str, key: Option<&str>, module_cid: Option<String>, confirmed: &Confirmed) {
+    let entry = journal::Entry {
+        timestamp: journal::Entry::now(),
+        profile: profile.name.clone(),
+        rpc_url: profile.rpc_url.clone(),
+        command: command.to_string(),
+        key: key.map(str::to_string),
+        module_cid,
+        signature: confirmed.signature.to_string(),
+        slot: confirmed.slot,
+        fee_lamports: confirmed.fee_lamports,
+    };
+    if let Err(e) = journal::append(&profile.journal_path, &entry) {
+        eprintln!("[WARN] {e}; {} is not in the local history.", entry.signature);
+    }
+}
+
+/// Record the outcome in the journal if the transaction was sent, then print it.
+fn report(profile: &Profile, command: &str, key: Option<&str>, outcome: &Outcome) {
+    if let Outcome::Sent(confirmed) = outcome {
+        record(profile, command, key, None, confirmed);
+    }
+    print_outcome(profile, outcome);
+}
+
+fn print_confirmed(profile: &Profile, confirmed: &Confirmed) {
+    let signature = confirmed.signature.to_string();
+    if output::is_json() {
+        output::print_json(&json!({ "signature": signature, "slot": confirmed.slot, "explorer_url": profile.explorer_url(&signature) }));
+        return
+    }
+    println!("Signature:\t\t{signature}");
+    if let Some(slot) = confirmed.slot {
+        println!("Slot:\t\t\t{slot}");
+    }
+    println!("Explorer:\t\t{}", profile.explorer_url(&signature));
+}
+
+fn print_outcome(profile: &Profile, outcome: &Outcome) {
+    match outcome {
+        Outcome::Sent(confirmed) => print_confirmed(profile, confirmed),
+        Outcome::DryRun(dry_run) => print_dry_run(dry_run),
+        Outcome::Exported(export) => print_export(export),
+    }
+}
+
+fn print_export(export: &client::Export) {
+    let transaction = &export.transaction;
+    let missing = transaction.missing_signers();
+    let to_stdout = export.path == Path::new("-");
+    let next = match (missing.is_empty(), to_stdout) {
+        (true, true) => "Broadcast it with `rhizo-cli submit <FILE>`.".to_string(),
+        (true, false) => format!("Broadcast it with `rhizo-cli submit {}`.", export.path.display()),
+        (false, true) => format!("Sign it with `rhizo-cli sign <FILE>` using the keypair of {}, then broadcast it with `rhizo-cli submit <FILE>`.", missing.join(", ")),
+        (false, false) => format!("Sign it with `rhizo-cli sign {0}` using the keypair of {1}, then broadcast it with `rhizo-cli submit {0}`.", export.path.display(), missing.join(", ")),
+    };
+    let expiry = match transaction.message().ok().and_then(|message| client::nonce_account(&solana_sdk::transaction::Transaction::new_unsigned(message))) {
+        Some(nonce) => format!("It uses nonce account {nonce} and stays valid until the nonce advances."),
+        None => "Its recent blockhash expires about a minute after export; submit it before then. Pass --durable-nonce to export one that does not expire.".to_string(),
+    };
+    if to_stdout {
+        // stdout carries the transaction itself.
+        eprintln!("{next}\n{expiry}");
+        return
+    }
+    if output::is_json() {
+        output::print_json(&json!({
+            "exported": export.path,
+            "action": transaction.action,
+            "recent_blockhash": transaction.recent_blockhash,
+            "fee_lamports": transaction.fee_lamports,
+            "rent_lamports": transaction.rent_lamports,
+            "missing_signatures": missing,
+        }));
+        return
+    }
+    println!("Exported:\t\t{}", export.path.display());
+    println!("Action:\t\t\t{}", transaction.action);
+    println!("Recent Blockhash:\t{}", transaction.recent_blockhash);
+    for (index, slot) in transaction.signers.iter().enumerate() {
+        let role = if index == 0 { "fee payer, " } else { "" };
+        println!("Signer:\t\t\t{} ({role}{})", slot.pubkey, if slot.signature.is_some() { "signed" } else { "not signed" });
+    }
+    println!("{next}");
+    println!("{expiry}");
+}
+
+fn print_dry_run(dry_run: &client::DryRun) {
+    if output::is_json() {
+        output::print_json(&json!({ "dry_run": dry_run }));
+        return
+    }
+    println!("Dry run: nothing was signed or sent.");
+    println!("Fee Payer:\t\t{}", dry_run.fee_payer);
+    println!("Owner:\t\t\t{}", dry_run.owner);
+    println!("Recent Blockhash:\t{}", dry_run.recent_blockhash);
+    println!("Fee:\t\t\t{}", output::format_lamports(dry_run.fee_lamports));
+    let budget = &dry_run.compute_budget;
+    if budget.compute_unit_price_micro_lamports > 0 {
+        println!("Priority Fee:\t\t{} lamports ({} micro-lamports x {} compute units)", budget.priority_fee_lamports, budget.compute_unit_price_micro_lamports, budget.compute_unit_limit);
+    }
+    println!("Rent:\t\t\t{}", output::format_lamports(dry_run.rent_lamports));
+    for rent in &dry_run.rent {
+        println!("\t\t\t{} lamports for the {} account {} ({} bytes, holds {} lamports)", rent.lamports, rent.account, rent.address, rent.size, rent.balance_lamports);
+    }
+    let needed = dry_run.fee_lamports + dry_run.rent_lamports;
+    println!("Payer Balance:\t\t{}", output::format_lamports(dry_run.payer_balance_lamports));
+    if dry_run.payer_balance_lamports < needed {
+        println!("[WARN] insufficient funds: need {}, have {}", output::format_lamports(needed), output::format_lamports(dry_run.payer_balance_lamports));
+    }
+    println!("Derived Addresses:");
+    for pda in &dry_run.derived_addresses {
+        println!("\t\t\t{} (seed {:?}, bump {})", pda.address, pda.seed, pda.bump_seed);
+    }
+    let _ = print_instructions(&mut std::io::stdout(), &dry_run.instructions);
+    match &dry_run.simulation.err {
+        None => println!("Simulation:\t\tsucceeded"),
+        Some(err) => println!("Simulation:\t\tfailed: {err}"),
+    }
+    if let Some(units) = dry_run.simulation.units_consumed {
+        println!("Units Consumed:\t\t{units}");
+    }
+    println!("Program Logs:");
+    for log in &dry_run.simulation.logs {
+        println!("\t{log}");
+    }
 }
 
-pub fn ocb_write(seed: &str, content: Vec<u8>) -> Result<(), Error> {
-    let keypair = utils::get_keypair()?;
-    let program_pubkey = Pubkey::from_str("Ep1SV45cqumZmogwWFy6pVNvMpRerMZUUhSJTbTh2e58")
-        .map_err(|_| Error::new("Unable to create program pubkey"))?;
-   
-    let connection = client::establish_connection().map_err(|_| Error::new("Unable to establish RPC connections"))?; 
-    client::write_ocb(&keypair, &program_pubkey, &connection, &rhizo_types::SignedOnchainBytesUpdate { seed: seed.to_string() , bytes: rhizo_types::SignedOnchainBytes { inner: content, owner_pubkey: keypair.pubkey().to_bytes() }, bump_seed: None, })    
+/// Print instructions in the JSON form of [`instruction::describe`] as text.
+fn print_instructions(out: &mut dyn Write, instructions: &[serde_json::Value]) -> std::io::Result<()> {
+    writeln!(out, "Instructions:")?;
+    for (index, instruction) in instructions.iter().enumerate() {
+        let name = instruction["data"]["name"].as_str().unwrap_or("Unknown");
+        match instruction["data"]["marker"].as_u64() {
+            Some(marker) => writeln!(out, "  #{index} {name} (marker {marker})")?,
+            None => writeln!(out, "  #{index} {name}")?,
+        }
+        writeln!(out, "\tprogram:\t{}", instruction["program_id"].as_str().unwrap_or_default())?;
+        for meta in instruction["accounts"].as_array().into_iter().flatten() {
+            let signer = if meta["is_signer"] == true { "signer" } else { "" };
+            let writable = if meta["is_writable"] == true { "writable" } else { "readonly" };
+            writeln!(out, "\taccount:\t{} {writable} {signer}", meta["pubkey"].as_str().unwrap_or_default())?;
+        }
+        writeln!(out, "\tpayload:\t{}", instruction["data"]["payload"])?;
+    }
+    Ok(())
 }
@@ -1,19 +1,219 @@
+use crate::{program_error, tls};
 use std::fmt;
 
-#[derive(Debug, Clone)]
-pub struct RhizoCLIError {
-    pub message: String
+/// Boxed underlying cause kept on every error so the CLI can print the full chain.
+pub type Source = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Hint for a rejected certificate, whether from an ingest or an RPC endpoint.
+const TLS_HINT: &str = "For a private CA pass --ca-file or run `rhizo-cli config set ca_file <bundle.pem>`. If the endpoint's key changed on purpose, update spki_pins in the rhizo config.";
+
+/// Broad category of a failure, used to pick the process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Anything not covered by a more specific kind, including user cancellation.
+    General,
+    /// A local check on a route config, WASM module or argument failed.
+    Validation,
+    /// The RPC node or ingest service could not be reached, failed TLS checks or returned an error.
+    Rpc,
+    /// The rhizo program rejected the transaction.
+    Program,
+}
+
+impl ErrorKind {
+    /// Process exit code for this kind. Usage errors exit with 2 from the argument parser.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorKind::General => 1,
+            ErrorKind::Validation => 3,
+            ErrorKind::Rpc => 4,
+            ErrorKind::Program => 5,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::General => "general",
+            ErrorKind::Validation => "validation",
+            ErrorKind::Rpc => "rpc",
+            ErrorKind::Program => "program",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RhizoCLIError {
+    /// A route config or CLI config file could not be read or parsed.
+    Config { message: String, source: Option<Source> },
+    /// The signing keypair could not be located or loaded.
+    Keypair { message: String, source: Option<Source> },
+    /// A local rule was violated by the command's input.
+    Validation { message: String, source: Option<Source> },
+    /// The RPC node could not be reached or rejected the request.
+    Rpc { message: String, source: Option<Source> },
+    /// The transaction was processed and failed inside the rhizo program.
+    Program { message: String, code: Option<u32>, source: Option<Source> },
+    /// The ingest service could not be reached or answered with a non-success status.
+    Ingest { message: String, status: Option<u16>, source: Option<Source> },
+    /// An HTTPS endpoint's certificate was rejected or the TLS handshake failed.
+    Tls { message: String, source: Option<Source> },
+    /// The WASM module failed to compile or instantiate.
+    Wasm { message: String, source: Option<Source> },
+    /// A local file or stream could not be read or written.
+    Io { message: String, source: Option<Source> },
+    /// The user declined or could not be asked to confirm.
+    Cancelled { message: String },
 }
 
 impl fmt::Display for RhizoCLIError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.message)
+        write!(f, "{}", self.message())
     }
 }
 
-impl std::error::Error for RhizoCLIError { }
+impl std::error::Error for RhizoCLIError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RhizoCLIError::Config { source, .. }
+            | RhizoCLIError::Keypair { source, .. }
+            | RhizoCLIError::Validation { source, .. }
+            | RhizoCLIError::Rpc { source, .. }
+            | RhizoCLIError::Program { source, .. }
+            | RhizoCLIError::Ingest { source, .. }
+            | RhizoCLIError::Tls { source, .. }
+            | RhizoCLIError::Wasm { source, .. }
+            | RhizoCLIError::Io { source, .. } => source.as_deref().map(|s| s as &(dyn std::error::Error + 'static)),
+            RhizoCLIError::Cancelled { .. } => None,
+        }
+    }
+}
 
 impl RhizoCLIError {
-    pub fn new(msg: &str) -> RhizoCLIError { RhizoCLIError { message: msg.to_string() } } 
+    pub fn config(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Config { message: msg.into(), source: None } }
+
+    pub fn keypair(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Keypair { message: msg.into(), source: None } }
+
+    pub fn validation(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Validation { message: msg.into(), source: None } }
+
+    pub fn rpc(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Rpc { message: msg.into(), source: None } }
+
+    pub fn program(msg: impl Into<String>, code: Option<u32>) -> RhizoCLIError { RhizoCLIError::Program { message: msg.into(), code, source: None } }
+
+    pub fn ingest(msg: impl Into<String>, status: Option<u16>) -> RhizoCLIError { RhizoCLIError::Ingest { message: msg.into(), status, source: None } }
+
+    pub fn tls(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Tls { message: msg.into(), source: None } }
+
+    pub fn wasm(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Wasm { message: msg.into(), source: None } }
+
+    pub fn io(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Io { message: msg.into(), source: None } }
+
+    pub fn cancelled(msg: impl Into<String>) -> RhizoCLIError { RhizoCLIError::Cancelled { message: msg.into() } }
+
+    /// Attach the underlying cause.
+    pub fn with_source(mut self, err: impl Into<Source>) -> RhizoCLIError {
+        match &mut self {
+            RhizoCLIError::Config { source, .. }
+            | RhizoCLIError::Keypair { source, .. }
+            | RhizoCLIError::Validation { source, .. }
+            | RhizoCLIError::Rpc { source, .. }
+            | RhizoCLIError::Program { source, .. }
+            | RhizoCLIError::Ingest { source, .. }
+            | RhizoCLIError::Tls { source, .. }
+            | RhizoCLIError::Wasm { source, .. }
+            | RhizoCLIError::Io { source, .. } => *source = Some(err.into()),
+            RhizoCLIError::Cancelled { .. } => {}
+        }
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            RhizoCLIError::Config { message, .. }
+            | RhizoCLIError::Keypair { message, .. }
+            | RhizoCLIError::Validation { message, .. }
+            | RhizoCLIError::Rpc { message, .. }
+            | RhizoCLIError::Program { message, .. }
+            | RhizoCLIError::Ingest { message, .. }
+            | RhizoCLIError::Tls { message, .. }
+            | RhizoCLIError::Wasm { message, .. }
+            | RhizoCLIError::Io { message, .. }
+            | RhizoCLIError::Cancelled { message } => message,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            RhizoCLIError::Config { .. }
+            | RhizoCLIError::Keypair { .. }
+            | RhizoCLIError::Validation { .. }
+            | RhizoCLIError::Wasm { .. } => ErrorKind::Validation,
+            RhizoCLIError::Rpc { .. } | RhizoCLIError::Ingest { .. } | RhizoCLIError::Tls { .. } => ErrorKind::Rpc,
+            RhizoCLIError::Program { .. } => ErrorKind::Program,
+            RhizoCLIError::Io { .. } | RhizoCLIError::Cancelled { .. } => ErrorKind::General,
+        }
+    }
+
+    /// Messages of every underlying cause, outermost first.
+    pub fn causes(&self) -> Vec<String> {
+        let mut causes = vec![];
+        let mut next = std::error::Error::source(self);
+        while let Some(cause) = next {
+            causes.push(cause.to_string());
+            next = cause.source();
+        }
+        causes
+    }
+
+    /// Suggested next step for the user, if there is a useful one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            RhizoCLIError::Config { .. } => Some("Fix the file named above. Route configs can be checked with `rhizo-cli validate-config <path>`, the Solana CLI config with `solana config get`."),
+            RhizoCLIError::Keypair { .. } => Some("Pass --keypair, set RHIZO_KEYPAIR or run `rhizo-cli config set keypair <path>` to point at a valid keypair file, or create one with `solana-keygen new`."),
+            RhizoCLIError::Rpc { source: Some(source), .. } if tls::is_failure(source.as_ref()) => Some(TLS_HINT),
+            RhizoCLIError::Rpc { .. } => Some("Check the RPC URL and your network connection, then retry."),
+            RhizoCLIError::Ingest { status: Some(413), .. } => Some("Reduce the module size, for example by building in release mode and stripping debug info."),
+            RhizoCLIError::Ingest { status: Some(status), .. } if *status >= 500 => Some("The ingest service is unavailable. Retry later; routes left without a module can be fixed with `rhizo-cli repair <module.wasm>`."),
+            RhizoCLIError::Ingest { .. } => Some("Check your network connection and the ingest endpoint, then retry."),
+            RhizoCLIError::Tls { .. } => Some(TLS_HINT),
+            RhizoCLIError::Wasm { .. } => Some("Make sure the module was built for the WASIX target (wasm32-wasmer-wasi)."),
+            RhizoCLIError::Io { .. } => Some("Check that the path exists and is readable."),
+            RhizoCLIError::Program { code: Some(code), .. } => program_error::lookup(*code).map(|entry| entry.remediation),
+            RhizoCLIError::Validation { .. } | RhizoCLIError::Program { .. } | RhizoCLIError::Cancelled { .. } => None,
+        }
+    }
+}
+
+/// A TOML parse failure reduced to its position and message, e.g.
+/// "line 3, column 14: unknown field `cache_ttl`".
+#[derive(Debug)]
+pub struct TomlError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl TomlError {
+    pub fn new(contents: &str, err: &toml::de::Error) -> TomlError {
+        let (line, column) = match err.span() {
+            Some(span) => {
+                let before = &contents[..span.start.min(contents.len())];
+                let line = before.matches('\n').count() + 1;
+                let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+                (line, column)
+            }
+            None => (0, 0),
+        };
+        TomlError { line, column, message: err.message().to_string() }
+    }
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}", self.message)
+        }
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
 }
 
+impl std::error::Error for TomlError { }
@@ -0,0 +1,317 @@
+use borsh::BorshSerialize;
+use crate::error::RhizoCLIError as Error;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use crate::profile::Profile;
+use crate::retry::{self, RetryPolicy};
+use crate::tls;
+use hyper::{Body, Client, Method, Request, Response, StatusCode};
+use hyper::client::HttpConnector;
+use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+use hyper_rustls::HttpsConnector;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Headers carrying an upload's provenance signature.
+pub const PUBKEY_HEADER: &str = "x-rhizo-pubkey";
+pub const SIGNATURE_HEADER: &str = "x-rhizo-signature";
+pub const ROUTE_HEADER: &str = "x-rhizo-route";
+pub const MODULE_CID_HEADER: &str = "x-rhizo-module-cid";
+pub const TIMESTAMP_HEADER: &str = "x-rhizo-timestamp";
+pub const NONCE_HEADER: &str = "x-rhizo-nonce";
+
+/// Domain separator at the start of every signed upload message.
+const SIGNED_MESSAGE_PREFIX: &str = "rhizo-ingest-upload-v1";
+
+/// A `RouteDeploy` serialized and gzipped for upload, signed by the developer keypair when it is
+/// available locally.
+pub struct Payload {
+    /// Size of the WASM module before compression.
+    pub module_size: usize,
+    /// Size of the serialized `RouteDeploy` before compression.
+    pub raw_size: usize,
+    pub gzipped: Vec<u8>,
+    /// `None` when only the developer's pubkey is known, e.g. when exporting for offline signing.
+    pub signature: Option<UploadSignature>,
+}
+
+impl Payload {
+    pub fn new(route_deploy: &rhizo_types::RouteDeploy, signer: &dyn Signer) -> Result<Payload, Error> {
+        let raw = route_deploy.try_to_vec().expect("route data serializes");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        let gzipped = encoder.write_all(&raw)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| Error::io("Unable to gzip the module").with_source(e))?;
+        let signature = UploadSignature::sign(signer, &route_deploy.metadata.route, &route_deploy.metadata.module_cid);
+        Ok(Payload { module_size: route_deploy.source.len(), raw_size: raw.len(), gzipped, signature })
+    }
+
+    /// Fail before anything is signed or sent if the upload is over the ingest service's limit.
+    pub fn check_size(&self, max_module_size: u64) -> Result<(), Error> {
+        if self.gzipped.len() as u64 > max_module_size {
+            return Err(Error::validation(format!(
+                "Module gzips to {} bytes ({} bytes raw), above the ingest limit of {max_module_size} bytes. Reduce the module size, for example by building in release mode and stripping debug info.",
+                self.gzipped.len(), self.module_size,
+            )))
+        }
+        Ok(())
+    }
+}
+
+/// Ed25519 signature tying an upload to the keypair that owns the route on-chain. It covers the
+/// route seed, the module CID, the signing time and a random nonce, see [`signed_message`].
+#[derive(Debug, Clone)]
+pub struct UploadSignature {
+    pub pubkey: Pubkey,
+    /// Route seed, including the `route-` prefix.
+    pub route: String,
+    pub module_cid: [u8; 32],
+    /// Unix time of signing, in seconds.
+    pub timestamp: u64,
+    pub nonce: [u8; 16],
+    pub signature: Signature,
+}
+
+impl UploadSignature {
+    /// Sign an upload, or `None` if `signer` cannot produce a valid signature, as a signer standing
+    /// in for an offline keypair cannot.
+    pub fn sign(signer: &dyn Signer, route: &str, module_cid: &[u8; 32]) -> Option<UploadSignature> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default();
+        let nonce = rand::random();
+        let signature = signer.try_sign_message(&signed_message(route, module_cid, timestamp, &nonce)).ok()?;
+        let signed = UploadSignature { pubkey: signer.pubkey(), route: route.to_string(), module_cid: *module_cid, timestamp, nonce, signature };
+        signed.verify().then_some(signed)
+    }
+
+    pub fn verify(&self) -> bool {
+        self.signature.verify(self.pubkey.as_ref(), &signed_message(&self.route, &self.module_cid, self.timestamp, &self.nonce))
+    }
+
+    /// Header names and values sent with the upload.
+    pub fn headers(&self) -> [(&'static str, String); 6] {
+        [
+            (PUBKEY_HEADER, self.pubkey.to_string()),
+            (SIGNATURE_HEADER, self.signature.to_string()),
+            (ROUTE_HEADER, self.route.clone()),
+            (MODULE_CID_HEADER, hex::encode(self.module_cid)),
+            (TIMESTAMP_HEADER, self.timestamp.to_string()),
+            (NONCE_HEADER, hex::encode(self.nonce)),
+        ]
+    }
+
+    /// Parse the values of the upload headers, looked up by lowercase name.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Result<UploadSignature, Error> {
+        let value = |name: &str| header(name)
+            .ok_or_else(|| Error::validation(format!("Missing the {name} header")));
+        let invalid = |name: &str| Error::validation(format!("The {name} header is malformed"));
+
+        let module_cid = hex::decode(value(MODULE_CID_HEADER)?).ok()
+            .and_then(|cid| <[u8; 32]>::try_from(cid).ok())
+            .ok_or_else(|| invalid(MODULE_CID_HEADER))?;
+        let nonce = hex::decode(value(NONCE_HEADER)?).ok()
+            .and_then(|nonce| <[u8; 16]>::try_from(nonce).ok())
+            .ok_or_else(|| invalid(NONCE_HEADER))?;
+        Ok(UploadSignature {
+            pubkey: Pubkey::from_str(value(PUBKEY_HEADER)?).map_err(|e| invalid(PUBKEY_HEADER).with_source(e))?,
+            route: value(ROUTE_HEADER)?.to_string(),
+            module_cid,
+            timestamp: value(TIMESTAMP_HEADER)?.parse().map_err(|e| invalid(TIMESTAMP_HEADER).with_source(e))?,
+            nonce,
+            signature: Signature::from_str(value(SIGNATURE_HEADER)?).map_err(|e| invalid(SIGNATURE_HEADER).with_source(e))?,
+        })
+    }
+}
+
+/// Bytes an upload signature covers, one field per line:
+/// `rhizo-ingest-upload-v1`, the route seed, the module CID in hex, the Unix timestamp in seconds
+/// and the nonce in hex.
+pub fn signed_message(route: &str, module_cid: &[u8; 32], timestamp: u64, nonce: &[u8; 16]) -> Vec<u8> {
+    format!("{SIGNED_MESSAGE_PREFIX}\n{route}\n{}\n{timestamp}\n{}", hex::encode(module_cid), hex::encode(nonce)).into_bytes()
+}
+
+/// How a module got onto an ingest endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upload {
+    Uploaded,
+    /// The endpoint already held the module, so it was not sent again.
+    AlreadyPresent,
+}
+
+/// Result of fetching a module back from an ingest endpoint by its CID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadBack {
+    /// The endpoint served the module and its blake3 hash matches the CID.
+    Verified,
+    /// The endpoint does not serve modules back, so the upload could not be checked.
+    Unsupported,
+}
+
+/// What happened at one ingest endpoint during a deploy or repair.
+#[derive(Debug)]
+pub struct Replica {
+    pub ingest_url: String,
+    pub upload: Result<Upload, Error>,
+    /// `None` when the upload failed and nothing was read back.
+    pub read_back: Option<Result<ReadBack, Error>>,
+}
+
+impl Replica {
+    /// The first failure at this endpoint, if any.
+    pub fn error(&self) -> Option<&Error> {
+        match (&self.upload, &self.read_back) {
+            (Err(e), _) | (_, Some(Err(e))) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// URL the ingest service serves a module from, e.g. `http://euro.rhizo.dev/ingest/<cid>`.
+pub fn module_url(ingest_url: &str, module_cid: &[u8; 32]) -> String {
+    format!("{}/{}", ingest_url.trim_end_matches('/'), iroh_blake3::Hash::from_bytes(*module_cid).to_hex())
+}
+
+/// HTTP(S) client for the profile's ingest endpoints, with its TLS settings and retry policy.
+pub struct IngestClient {
+    http: Client<HttpsConnector<HttpConnector>>,
+    policy: RetryPolicy,
+}
+
+impl IngestClient {
+    pub fn new(profile: &Profile) -> Result<IngestClient, Error> {
+        Ok(IngestClient { http: Client::builder().build(tls::connector(profile)?), policy: profile.retry })
+    }
+
+    /// POST a gzipped module and its route metadata to an ingest endpoint, retrying transient failures.
+    pub async fn upload(&self, ingest_url: &str, payload: &Payload) -> Result<(), Error> {
+        self.policy.run_async("Uploading the module", || self.upload_once(ingest_url, payload), retry::is_retryable_ingest).await
+    }
+
+    async fn upload_once(&self, ingest_url: &str, payload: &Payload) -> Result<(), Error> {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(ingest_url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_ENCODING, "gzip");
+        for (name, value) in payload.signature.iter().flat_map(UploadSignature::headers) {
+            request = request.header(name, value);
+        }
+        let request = request
+            .body(Body::from(payload.gzipped.clone()))
+            .map_err(|e| Error::ingest("Unable to build the ingest request", None).with_source(e))?;
+
+        let response = self.send(request).await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let body_bytes = hyper::body::to_bytes(response.into_body()).await
+                    .map_err(|e| Error::ingest("Unable to read response bytes from Rhizo server", Some(200)).with_source(e))?;
+                if let Err(e) = std::str::from_utf8(&body_bytes) {
+                    return Err(Error::ingest("Unable to parse response body as UTF-8", Some(200)).with_source(e))
+                }
+                Ok(())
+            }
+            StatusCode::PAYLOAD_TOO_LARGE => Err(Error::ingest("Payload too large. WASM module must gzip to less than 2mb.", Some(413))),
+            status => Err(Error::ingest(format!("Error from rhizo server {:?}", status.as_str()), Some(status.as_u16()))),
+        }
+    }
+
+    /// Upload a module to every ingest endpoint concurrently, then read it back from each to check
+    /// that the endpoint holds what was sent. With `skip_present`, endpoints that already hold the
+    /// module are only read back.
+    pub async fn replicate(&self, ingest_urls: &[String], payload: &Payload, module_cid: &[u8; 32], skip_present: bool) -> Vec<Replica> {
+        for ingest_url in ingest_urls.iter().filter(|url| url.starts_with("http://")) {
+            eprintln!("[WARN] {ingest_url} is not HTTPS; the module and route metadata are sent to it unencrypted.");
+        }
+        futures::future::join_all(ingest_urls.iter().map(|ingest_url| async move {
+            let upload = match skip_present && self.has_module(ingest_url, module_cid).await.unwrap_or(false) {
+                true => Ok(Upload::AlreadyPresent),
+                false => self.upload(ingest_url, payload).await.map(|_| Upload::Uploaded),
+            };
+            let read_back = match upload {
+                Ok(_) => Some(self.read_back(ingest_url, module_cid).await),
+                Err(_) => None,
+            };
+            Replica { ingest_url: ingest_url.clone(), upload, read_back }
+        })).await
+    }
+
+    /// Fetch a module from an ingest endpoint by CID and check its blake3 hash. Endpoints that
+    /// answer 405 or 501 do not serve modules back and are reported as unsupported.
+    pub async fn read_back(&self, ingest_url: &str, module_cid: &[u8; 32]) -> Result<ReadBack, Error> {
+        let url = module_url(ingest_url, module_cid);
+        let module = self.policy.run_async("Reading the module back", || async {
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(url.as_str())
+                .header(ACCEPT_ENCODING, "gzip")
+                .body(Body::empty())
+                .map_err(|e| Error::ingest(format!("Unable to build a request for {url}"), None).with_source(e))?;
+
+            let response = self.send(request).await?;
+            match response.status() {
+                status if status.is_success() => {
+                    let gzipped = response.headers().get(CONTENT_ENCODING).is_some_and(|encoding| encoding == "gzip");
+                    let body = hyper::body::to_bytes(response.into_body()).await
+                        .map_err(|e| Error::ingest(format!("Unable to read the module from {url}"), Some(status.as_u16())).with_source(e))?;
+                    if !gzipped {
+                        return Ok(Some(body.to_vec()))
+                    }
+                    let mut module = vec![];
+                    GzDecoder::new(body.as_ref()).read_to_end(&mut module)
+                        .map_err(|e| Error::ingest(format!("Unable to gunzip the module from {url}"), Some(status.as_u16())).with_source(e))?;
+                    Ok(Some(module))
+                }
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => Ok(None),
+                StatusCode::NOT_FOUND => Err(Error::ingest(format!("{url} does not have the module"), Some(404))),
+                status => Err(Error::ingest(format!("Unexpected status {} reading back {url}", status.as_str()), Some(status.as_u16()))),
+            }
+        }, retry::is_retryable_ingest).await?;
+
+        let Some(module) = module else { return Ok(ReadBack::Unsupported) };
+        let hash = iroh_blake3::hash(&module);
+        if hash.as_bytes() != module_cid {
+            return Err(Error::ingest(format!(
+                "{url} served {} bytes hashing to {hash}, not the uploaded module {}",
+                module.len(), iroh_blake3::Hash::from_bytes(*module_cid),
+            ), None))
+        }
+        Ok(ReadBack::Verified)
+    }
+
+    /// Whether an ingest endpoint already holds the module with `module_cid`.
+    pub async fn has_module(&self, ingest_url: &str, module_cid: &[u8; 32]) -> Result<bool, Error> {
+        let url = module_url(ingest_url, module_cid);
+        self.policy.run_async("Looking up the module", || async {
+            let request = Request::builder()
+                .method(Method::HEAD)
+                .uri(url.as_str())
+                .body(Body::empty())
+                .map_err(|e| Error::ingest(format!("Unable to build a request for {url}"), None).with_source(e))?;
+
+            match self.send(request).await?.status() {
+                status if status.is_success() => Ok(true),
+                StatusCode::NOT_FOUND => Ok(false),
+                status => Err(Error::ingest(format!("Unexpected status {} looking up {url}", status.as_str()), Some(status.as_u16()))),
+            }
+        }, retry::is_retryable_ingest).await
+    }
+
+    /// Send one request, failing if no response arrives within the policy's timeout. Certificate
+    /// and handshake failures become TLS errors, which are never retried.
+    async fn send(&self, request: Request<Body>) -> Result<Response<Body>, Error> {
+        let host = request.uri().host().unwrap_or_default().to_string();
+        let timeout = self.policy.timeout;
+        match tokio::time::timeout(timeout, self.http.request(request)).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) => match tls::describe_failure(&e, &host) {
+                Some(reason) => Err(Error::tls(format!("TLS with the ingest service at {host} failed: {reason}")).with_source(e)),
+                None => Err(Error::ingest("Unable to reach the rhizo ingest service", None).with_source(e)),
+            },
+            Err(e) => Err(Error::ingest(format!("The rhizo ingest service did not respond within {}s", timeout.as_secs()), None).with_source(e)),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use borsh::BorshDeserialize;
+use crate::error::RhizoCLIError as Error;
+use serde_json::json;
+use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::system_program;
+
+/// Stores a route's data in its PDA. Payload: `RouteData`.
+pub const ROUTE_DATA: u64 = 0;
+/// Adds or removes a route in the developer's route list. Payload: `RouteUpdate`.
+pub const ROUTE_UPDATE_REQUEST: u64 = 1;
+/// Allocates signed on-chain bytes. Payload: `SignedOnchainBytesUpdate`.
+pub const ALLOC_SOCB: u64 = 2;
+/// Overwrites signed on-chain bytes. Payload: `SignedOnchainBytesUpdate`.
+pub const WRITE_SOCB: u64 = 3;
+/// Closes a route account. Payload: the account balance as a little-endian u64.
+pub const DELETE: u64 = 4;
+/// Adds a seed to the developer's signed on-chain bytes list. Payload: the list PDA bump seed
+/// followed by `ListSignedOnchainBytesUpdate`.
+pub const LIST_SOCB: u64 = 5;
+/// Removes a route and closes its account. Payload: `RouteUpdate`.
+pub const YANK: u64 = 6;
+
+pub fn name(marker: u64) -> &'static str {
+    match marker {
+        ROUTE_DATA => "RouteData",
+        ROUTE_UPDATE_REQUEST => "RouteUpdateRequest",
+        ALLOC_SOCB => "AllocSignedOnchainBytes",
+        WRITE_SOCB => "WriteSignedOnchainBytes",
+        DELETE => "Delete",
+        LIST_SOCB => "ListSignedOnchainBytes",
+        YANK => "Yank",
+        _ => "Unknown",
+    }
+}
+
+/// Instruction data for `marker` followed by `payload`.
+pub fn data(marker: u64, payload: &[u8]) -> Vec<u8> {
+    let mut data = marker.to_le_bytes().to_vec();
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Decode rhizo instruction data into its marker, name and Borsh payload.
+pub fn decode(data: &[u8]) -> Result<serde_json::Value, Error> {
+    if data.len() < 8 {
+        return Err(Error::validation(format!("Instruction data is {} bytes, too short for a marker", data.len())))
+    }
+    let marker = u64::from_le_bytes(data[..8].try_into().expect("slice is 8 bytes"));
+    let payload = &data[8..];
+    let malformed = |e: std::io::Error| Error::validation(format!("Unable to decode the {} payload", name(marker))).with_source(e);
+
+    let decoded = match marker {
+        ROUTE_DATA => route_data_json(&rhizo_types::RouteData::try_from_slice(payload).map_err(malformed)?),
+        ROUTE_UPDATE_REQUEST | YANK => {
+            let update = rhizo_types::RouteUpdate::try_from_slice(payload).map_err(malformed)?;
+            json!({ "route": update.route, "bump_seed": update.bump_seed, "operation": update.operation })
+        }
+        ALLOC_SOCB | WRITE_SOCB => {
+            let update = rhizo_types::SignedOnchainBytesUpdate::try_from_slice(payload).map_err(malformed)?;
+            json!({
+                "seed": update.seed,
+                "bump_seed": update.bump_seed,
+                "owner_pubkey": Pubkey::new_from_array(update.bytes.owner_pubkey).to_string(),
+                "len": update.bytes.inner.len(),
+                "bytes_hex": hex::encode(&update.bytes.inner),
+            })
+        }
+        DELETE => {
+            let lamports: [u8; 8] = payload.try_into()
+                .map_err(|_| Error::validation(format!("Delete payload is {} bytes, expected 8", payload.len())))?;
+            json!({ "lamports": u64::from_le_bytes(lamports) })
+        }
+        LIST_SOCB => {
+            let (bump_seed, rest) = payload.split_first()
+                .ok_or_else(|| Error::validation("ListSignedOnchainBytes payload is empty"))?;
+            let list = rhizo_types::ListSignedOnchainBytesUpdate::try_from_slice(rest).map_err(malformed)?;
+            json!({ "bump_seed": bump_seed, "seed": list.seed })
+        }
+        _ => json!({ "data_hex": hex::encode(payload) }),
+    };
+    Ok(json!({ "marker": marker, "name": name(marker), "payload": decoded }))
+}
+
+/// Decode ComputeBudget program instruction data into its name and arguments.
+pub fn decode_compute_budget(data: &[u8]) -> Result<serde_json::Value, Error> {
+    let instruction = ComputeBudgetInstruction::try_from_slice(data)
+        .map_err(|e| Error::validation("Unable to decode the ComputeBudget instruction").with_source(e))?;
+    Ok(match instruction {
+        ComputeBudgetInstruction::SetComputeUnitLimit(units) => json!({ "name": "SetComputeUnitLimit", "payload": { "units": units } }),
+        ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports) => json!({ "name": "SetComputeUnitPrice", "payload": { "micro_lamports": micro_lamports } }),
+        ComputeBudgetInstruction::RequestHeapFrame(bytes) => json!({ "name": "RequestHeapFrame", "payload": { "bytes": bytes } }),
+        ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(bytes) => json!({ "name": "SetLoadedAccountsDataSizeLimit", "payload": { "bytes": bytes } }),
+        other => json!({ "name": format!("{other:?}"), "payload": null }),
+    })
+}
+
+/// Decode the System program instructions rhizo-cli sends into their name and arguments.
+pub fn decode_system(data: &[u8]) -> Result<serde_json::Value, Error> {
+    let instruction: SystemInstruction = bincode::deserialize(data)
+        .map_err(|e| Error::validation("Unable to decode the System instruction").with_source(e))?;
+    Ok(match instruction {
+        SystemInstruction::Transfer { lamports } => json!({ "name": "Transfer", "payload": { "lamports": lamports } }),
+        SystemInstruction::AdvanceNonceAccount => json!({ "name": "AdvanceNonceAccount", "payload": null }),
+        SystemInstruction::WithdrawNonceAccount(lamports) => json!({ "name": "WithdrawNonceAccount", "payload": { "lamports": lamports } }),
+        SystemInstruction::InitializeNonceAccount(authority) => json!({ "name": "InitializeNonceAccount", "payload": { "authority": authority.to_string() } }),
+        SystemInstruction::CreateAccountWithSeed { base, seed, lamports, space, owner } => json!({
+            "name": "CreateAccountWithSeed",
+            "payload": { "base": base.to_string(), "seed": seed, "lamports": lamports, "space": space, "owner": owner.to_string() },
+        }),
+        other => json!({ "name": format!("{other:?}"), "payload": null }),
+    })
+}
+
+/// JSON form of an instruction: its program, account metas and decoded data.
+pub fn describe(instruction: &Instruction) -> serde_json::Value {
+    let decoded = match instruction.program_id {
+        program_id if program_id == compute_budget::id() => decode_compute_budget(&instruction.data),
+        program_id if program_id == system_program::id() => decode_system(&instruction.data),
+        _ => decode(&instruction.data),
+    };
+    let decoded = decoded
+        .unwrap_or_else(|e| json!({ "error": e.message(), "data_hex": hex::encode(&instruction.data) }));
+    json!({
+        "program_id": instruction.program_id.to_string(),
+        "accounts": instruction.accounts.iter().map(|meta| json!({
+            "pubkey": meta.pubkey.to_string(),
+            "is_signer": meta.is_signer,
+            "is_writable": meta.is_writable,
+        })).collect::<Vec<_>>(),
+        "data": decoded,
+    })
+}
+
+/// The instructions of a compiled message, with their account metas restored.
+pub fn decompile(message: &Message) -> Vec<Instruction> {
+    message.instructions.iter().map(|compiled| Instruction {
+        program_id: message.account_keys[compiled.program_id_index as usize],
+        accounts: compiled.accounts.iter().map(|&index| AccountMeta {
+            pubkey: message.account_keys[index as usize],
+            is_signer: message.is_signer(index as usize),
+            is_writable: message.is_writable(index as usize),
+        }).collect(),
+        data: compiled.data.clone(),
+    }).collect()
+}
+
+/// JSON form of on-chain route data shared by `view route`, `deploy` and instruction decoding.
+pub fn route_data_json(route_data: &rhizo_types::RouteData) -> serde_json::Value {
+    json!({
+        "route": route_data.route,
+        "module_cid": iroh_blake3::Hash::from_bytes(route_data.module_cid).to_hex().to_string(),
+        "encodings": route_data.encodings,
+        "arguments": route_data.arguments.iter().map(|arg| json!({
+            "name": String::from_utf8_lossy(&arg.0),
+            "argument_type": arg.1,
+        })).collect::<Vec<_>>(),
+        "cache_config": {
+            "cacheable": route_data.cache_config.0,
+            "cache_ttl_ms": route_data.cache_config.1,
+        },
+        "bump_seed": route_data.bump_seed,
+    })
+}
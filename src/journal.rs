@@ -0,0 +1,65 @@
+use crate::error::RhizoCLIError as Error;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One confirmed transaction in the local journal, stored as a line of JSON.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix time the transaction was confirmed, in seconds.
+    pub timestamp: u64,
+    pub profile: String,
+    pub rpc_url: String,
+    /// Command that sent the transaction, e.g. "deploy" or "socb write".
+    pub command: String,
+    /// Route or signed on-chain bytes seed the transaction changed, if any.
+    pub key: Option<String>,
+    /// CID of the deployed module in hex, for deploys.
+    pub module_cid: Option<String>,
+    pub signature: String,
+    pub slot: Option<u64>,
+    /// Estimated fee, including any priority fee.
+    pub fee_lamports: u64,
+}
+
+impl Entry {
+    pub fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+    }
+}
+
+/// Append `entry` to the journal at `path`, creating it if needed.
+pub fn append(path: &Path, entry: &Entry) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| Error::io(format!("Unable to create {}", dir.display())).with_source(e))?;
+    }
+    let line = serde_json::to_string(entry).expect("journal entry serializes");
+    std::fs::OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| writeln!(file, "{line}"))
+        .map_err(|e| Error::io(format!("Unable to append to the journal at {}", path.display())).with_source(e))
+}
+
+/// Read the journal at `path`, oldest entry first. A missing journal is empty.
+pub fn read(path: &Path) -> Result<Vec<Entry>, Error> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(Error::io(format!("Unable to read the journal at {}", path.display())).with_source(e)),
+    };
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| serde_json::from_str(line)
+            .map_err(|e| Error::validation(format!("Line {} of the journal at {} is not a journal entry", index + 1, path.display())).with_source(e)))
+        .collect()
+}
+
+/// Format Unix seconds as `YYYY-MM-DD HH:MM:SS` in UTC, or as the plain number if out of range.
+pub fn format_time(timestamp: u64) -> String {
+    i64::try_from(timestamp).ok()
+        .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
@@ -1,144 +1,202 @@
+// RPC calls return solana_client's ClientError, which is larger than clippy's default limit.
+#![allow(clippy::result_large_err)]
+
+mod cli;
 mod client;
 mod utils;
 mod commands;
 mod error;
+mod ingest;
+mod instruction;
+mod journal;
+mod offline;
+mod output;
+mod profile;
+mod program_error;
+mod retry;
+mod schema;
+mod tls;
 mod types;
 
+use crate::cli::{Cli, Command, ConfigCommand, NonceCommand, ProfileArgs, SocbCommand, TransactionArgs, ViewCommand};
+use crate::client::TransactionOptions;
 use crate::commands::*;
+use crate::error::RhizoCLIError as Error;
+use crate::output::OutputFormat;
+use crate::profile::ProfileOverrides;
+use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn print_help(){
-    println!("rhizo-cli\n");
-    println!("Commands");
-    println!("deploy $wasm_module_path $route_config_path\tDeploy a route configuration and backing WASM module. Both should validate locally.");
-    println!("help\t\t\t\t\t\tView the help information for this tool.");
-    println!("ls [socb | route]\t\t\t\tFetch the list of the developer's hosted routes or signed onchain bytes.");   
-    println!("socb alloc $key $num_bytes\t\t\tAllocate signed on-chain bytes owned by the current keypair.");
-    println!("socb write $key $content_path\t\t\tWrite signed on-chain bytes owned by the current keypair.");
-    println!("validate-config $route_config_path\t\tValidate a route configuration file");   
-    println!("validate-module $wasm_module_path\t\tValidate a compiled WASIX WASM file. Only detects ABI compatibility with rhizo-server, not runtime errors.");        
-    println!("version\t\t\t\t\t\tView the rhizo-cli version.");
-    println!("view [socb | route] $key\t\t\tFetch a route or signed onchain bytes by name.");
-    println!("test-module $wasm_module_path\t\t\tExecute the module's test() function locally. Useful for local testing and finding runtime errors.");
-    println!("yank $route_key\t\t\t\t\tInitiate yanking a route from the network.")
+impl From<&TransactionArgs> for TransactionOptions {
+    fn from(args: &TransactionArgs) -> Self {
+        TransactionOptions {
+            yes: args.yes,
+            max_fee_lamports: args.max_fee_lamports,
+            priority_fee: args.priority_fee,
+            compute_unit_limit: args.compute_unit_limit,
+            max_rent_lamports: args.max_rent_lamports,
+            dry_run: args.dry_run,
+            export_tx: args.export_tx.clone().or_else(|| args.sign_only.then(|| PathBuf::from("-"))),
+            sign_only: args.sign_only,
+            nonce: args.durable_nonce,
+            nonce_authority: args.nonce_authority.clone(),
+            fee_payer: args.fee_payer.clone(),
+        }
+    }
 }
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
+impl From<&ProfileArgs> for ProfileOverrides {
+    fn from(args: &ProfileArgs) -> Self {
+        ProfileOverrides {
+            config_path: args.config.clone(),
+            profile: args.profile.clone(),
+            rpc_url: args.url.clone(),
+            keypair: args.keypair.clone(),
+            program_id: args.program_id.clone(),
+            attempts: args.attempts,
+            timeout_secs: args.timeout,
+            ca_file: args.ca_file.clone(),
+        }
+    }
+}
 
-#[tokio::main]
-async fn main() -> Result<(), ()> {
-    let args: Vec<String> = std::env::args().collect();
-    match args.get(1) {
-        Some(command) => {
-            if command.to_lowercase().eq("deploy"){
-                let try_wasm_path = args.get(2);
-                let try_api_config_path = args.get(3);
-                match (try_wasm_path, try_api_config_path) {
-                    (Some(wasm_path), Some(config_path)) => {
-                        match deploy(wasm_path, config_path, 0u8).await {
-                            Ok(_) => {}
-                            Err(err) => {
-                                eprintln!("[ERROR] deploy failed due to error: {}", err.message)
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            } else
-            if command.to_lowercase().eq("yank"){
-                let try_seed = args.get(2);
-                match try_seed {
-                    Some(seed) => {
-                        match yank_route(seed) {
-                            Ok(_) => { }
-                            Err(err) => {
-                                eprintln!("[ERROR] yank failed due to error: {}", err.message)
-                            }
-                        }
-                    }
-                    _ => { println!("Missing arguments") }
-                }
-            }
-            else if command.to_lowercase().eq("view"){
-                let collection = args.get(2).unwrap();
-                let try_seed = args.get(3);
-                match try_seed {
-                    Some(seed) => {
-                        match view(collection, seed) {
-                            Ok(_) => {}
-                            Err(err) => {
-                                eprintln!("[ERROR] view failed due to error: {}", err.message)
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            else if command.to_lowercase().eq("ls"){
-                    let collection = args.get(2).unwrap();
-                        match ls(collection) {
-                            Ok(_) => {}
-                            Err(err) => {
-                                eprintln!("[ERROR] ls failed due to error: {}", err.message)
-                            }
-                        }
-            }
-            else if command.to_lowercase().eq("validate-config"){
-                let try_cid = args.get(2);
-                match try_cid {
-                    Some(cid) => {
-                        match validate_config(cid).await {
-                            Ok(_) => {}
-                            Err(err) => {
-                                eprintln!("[ERROR] view failed due to error: {}", err.message)
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            } else if command.to_lowercase().eq("validate-module"){
-                let try_cid = args.get(2);
-                match try_cid {
-                    Some(cid) => {
-                        match validate_module(cid).await {
-                            Ok(_) => {}
-                            Err(err) => {
-                                eprintln!("[ERROR] view failed due to error: {}", err.message)
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            } else if command.to_lowercase().eq("socb") { 
-                let ocb_cmd = args.get(2).unwrap();
-                let ocb_seed = args.get(3).unwrap();
-                let ocb_content = args.get(4).unwrap();
-               
-                if ocb_cmd.eq("write"){
-                    match ocb_write(ocb_seed, ocb_content.as_bytes().to_vec()) {
-                        Err(e) => println!("[ERROR] socb write failed due to: {}", e),
-                        Ok(_) => {}
-                    } 
-                } else if ocb_cmd.eq("alloc") {
-                    match ocb_alloc(ocb_seed, 32) {
-                        Err(e) => println!("[ERROR] socb alloc failed due to: {}", e),
-                        Ok(_) => {}
-                    }
-                }
-            } else if command.to_lowercase().eq("test-module") {
-                test_module(args.get(2).unwrap());
-            } else if command.to_lowercase().eq("-h") || command.to_lowercase().eq("--help") || command.to_lowercase().eq("help") { 
-                print_help();
-            } else if command.to_lowercase().eq("-v") || command.to_lowercase().eq("--version") || command.to_lowercase().eq("version") { 
-                println!("rhizo-cli {VERSION}");
+async fn run(command: Command, overrides: &ProfileOverrides, options: &TransactionOptions) -> Result<(), (&'static str, Error)> {
+    let load_profile = |name| profile::load(overrides).map_err(|e| (name, e));
+    match command {
+        Command::Deploy { wasm_module_path, route_config_path, force } => {
+            let profile = load_profile("deploy")?;
+            deploy(&profile, &wasm_module_path, &route_config_path, 0u8, force, options).await.map_err(|e| ("deploy", e))
+        }
+        Command::Repair { wasm_module_paths } => {
+            let profile = load_profile("repair")?;
+            repair(&profile, &wasm_module_paths).await.map_err(|e| ("repair", e))
+        }
+        Command::Yank { route_key } => {
+            let profile = load_profile("yank")?;
+            yank_route(&profile, &route_key, options).map_err(|e| ("yank", e))
+        }
+        Command::View { command: ViewCommand::Route { key } } => {
+            let profile = load_profile("view route")?;
+            view_route(&profile, &key).map_err(|e| ("view route", e))
+        }
+        Command::View { command: ViewCommand::Socb { key, display } } => {
+            let profile = load_profile("view socb")?;
+            view_socb(&profile, &key, &display).map_err(|e| ("view socb", e))
+        }
+        Command::Ls { collection } => {
+            let profile = load_profile("ls")?;
+            ls(&profile, collection).map_err(|e| ("ls", e))
+        }
+        Command::Socb { command: SocbCommand::Alloc { key, num_bytes } } => {
+            let profile = load_profile("socb alloc")?;
+            ocb_alloc(&profile, &key, num_bytes, options).map_err(|e| ("socb alloc", e))
+        }
+        Command::Socb { command: SocbCommand::Resize { key, num_bytes, force } } => {
+            let profile = load_profile("socb resize")?;
+            ocb_resize(&profile, &key, num_bytes, force, options).map_err(|e| ("socb resize", e))
+        }
+        Command::Socb { command: SocbCommand::Cat { key } } => {
+            let profile = load_profile("socb cat")?;
+            ocb_cat(&profile, &key).map_err(|e| ("socb cat", e))
+        }
+        Command::Socb { command: SocbCommand::Write { key, content, schema } } => {
+            let profile = load_profile("socb write")?;
+            ocb_write(&profile, &key, &content, schema.as_deref(), options).map_err(|e| ("socb write", e))
+        }
+        Command::Config { command: ConfigCommand::Show } => {
+            config_show(overrides).map_err(|e| ("config show", e))
+        }
+        Command::Config { command: ConfigCommand::Set { key, value } } => {
+            config_set(overrides, &key, &value).map_err(|e| ("config set", e))
+        }
+        Command::Config { command: ConfigCommand::Use { profile } } => {
+            config_use(overrides, &profile).map_err(|e| ("config use", e))
+        }
+        Command::ValidateConfig { route_config_path } => {
+            validate_config(&route_config_path).await.map_err(|e| ("validate-config", e))
+        }
+        Command::ValidateModule { wasm_module_path } => {
+            validate_module(&wasm_module_path).await.map_err(|e| ("validate-module", e))
+        }
+        Command::TestModule { wasm_module_path } => {
+            test_module(&wasm_module_path).map_err(|e| ("test-module", e))
+        }
+        Command::ExplainError { code } => {
+            explain_error(code.as_deref()).map_err(|e| ("explain-error", e))
+        }
+        Command::Nonce { command: NonceCommand::Create { seed, lamports } } => {
+            let profile = load_profile("nonce create")?;
+            nonce_create(&profile, &seed, lamports, options).map_err(|e| ("nonce create", e))
+        }
+        Command::Nonce { command: NonceCommand::Show { account } } => {
+            let profile = load_profile("nonce show")?;
+            nonce_show(&profile, &account).map_err(|e| ("nonce show", e))
+        }
+        Command::Nonce { command: NonceCommand::Withdraw { lamports, account, to } } => {
+            let profile = load_profile("nonce withdraw")?;
+            nonce_withdraw(&profile, &account, lamports, to, options).map_err(|e| ("nonce withdraw", e))
+        }
+        Command::Sign { transaction_path, out } => {
+            let profile = load_profile("sign")?;
+            sign(&profile, &transaction_path, out.as_deref(), options).map_err(|e| ("sign", e))
+        }
+        Command::Submit { transaction_path } => {
+            let profile = load_profile("submit")?;
+            submit(&profile, &transaction_path).map_err(|e| ("submit", e))
+        }
+        Command::History { local, key, limit } => {
+            let profile = load_profile("history")?;
+            history(&profile, local, key.as_deref(), limit).map_err(|e| ("history", e))
+        }
+        Command::VerifyUpload { headers, fields, module, max_age } => {
+            verify_upload(headers.as_deref(), &fields, module.as_deref(), max_age).map_err(|e| ("verify-upload", e))
+        }
+        Command::Version => {
+            if output::is_json() {
+                output::print_json(&serde_json::json!({ "version": VERSION }));
             } else {
-                println!("[ERROR] unsupported command {:?}", command);
-                print_help();
+                println!("rhizo-cli {VERSION}");
             }
+            Ok(())
+        }
+    }
+}
+
+/// Whether `--output json` was requested, for reporting errors the argument parser rejects.
+fn json_requested(args: &[String]) -> bool {
+    args.windows(2).any(|pair| (pair[0] == "--output" || pair[0] == "-o") && pair[1] == "json")
+        || args.iter().any(|arg| arg == "--output=json" || arg == "-ojson")
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(err) if err.use_stderr() && json_requested(&args) => {
+            output::print_json(&serde_json::json!({
+                "error": { "command": null, "kind": "usage", "message": err.to_string().trim_end(), "exit_code": 2 }
+            }));
+            return ExitCode::from(2)
         }
-        _ => {
-            println!("[ERROR] missing command");
-            print_help();
+        Err(err) => err.exit(),
+    };
+    let overrides = ProfileOverrides::from(&cli.profile);
+    let options = TransactionOptions::from(&cli.transaction);
+    // An exported transaction written to stdout is the command's document, so everything else
+    // goes to stderr as in JSON mode.
+    match options.export_tx.as_deref() == Some(Path::new("-")) {
+        true => output::init(OutputFormat::Json),
+        false => output::init(cli.output),
+    }
+    match run(cli.command, &overrides, &options).await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err((command, err)) => {
+            output::print_error(command, &err);
+            ExitCode::from(err.kind().exit_code())
         }
-    } 
-    Ok(())
+    }
 }
@@ -0,0 +1,139 @@
+use base64::Engine;
+use crate::error::RhizoCLIError as Error;
+use serde::{Deserialize, Serialize};
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sanitize::Sanitize;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::transaction::Transaction;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Version of the exported transaction format, bumped on incompatible changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A transaction written by `--export-tx`, signed with `rhizo-cli sign` and broadcast with
+/// `rhizo-cli submit`. Only `message` and the signatures are trusted when signing or submitting;
+/// the other fields are notes from the exporting machine.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedTransaction {
+    pub version: u32,
+    /// What the transaction does, e.g. "Yank route operation".
+    pub action: String,
+    /// Fee and rent estimated when the transaction was exported.
+    pub fee_lamports: u64,
+    pub rent_lamports: u64,
+    pub recent_blockhash: String,
+    /// Every required signer in message order, with its base58 signature once signed.
+    pub signers: Vec<SignerSlot>,
+    /// Base64 of the bincode-serialized message. This is what gets signed.
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignerSlot {
+    pub pubkey: String,
+    pub signature: Option<String>,
+}
+
+impl ExportedTransaction {
+    pub fn new(action: &str, message: &Message, fee_lamports: u64, rent_lamports: u64) -> ExportedTransaction {
+        let signers = message.account_keys[..message.header.num_required_signatures as usize].iter()
+            .map(|pubkey| SignerSlot { pubkey: pubkey.to_string(), signature: None })
+            .collect();
+        ExportedTransaction {
+            version: FORMAT_VERSION,
+            action: action.to_string(),
+            fee_lamports,
+            rent_lamports,
+            recent_blockhash: message.recent_blockhash.to_string(),
+            signers,
+            message: base64::engine::general_purpose::STANDARD.encode(message.serialize()),
+        }
+    }
+
+    /// Decode and sanitize the message, and check that the signer list and blockhash describe it.
+    pub fn message(&self) -> Result<Message, Error> {
+        if self.version != FORMAT_VERSION {
+            return Err(Error::validation(format!("Unsupported exported transaction version {}, expected {FORMAT_VERSION}", self.version)))
+        }
+        let bytes = base64::engine::general_purpose::STANDARD.decode(&self.message)
+            .map_err(|e| Error::validation("The exported message is not valid base64").with_source(e))?;
+        let message: Message = bincode::deserialize(&bytes)
+            .map_err(|e| Error::validation("The exported message is not a Solana message").with_source(e))?;
+        // Out-of-range account indexes would otherwise panic when the message is reviewed or signed.
+        message.sanitize()
+            .map_err(|e| Error::validation("The exported message is malformed; the file was modified or corrupted").with_source(e))?;
+
+        let required = message.account_keys.get(..message.header.num_required_signatures as usize).unwrap_or_default();
+        if required.iter().map(Pubkey::to_string).ne(self.signers.iter().map(|slot| slot.pubkey.clone())) {
+            return Err(Error::validation("The signer list does not match the message's required signers; the file was modified"))
+        }
+        if message.recent_blockhash.to_string() != self.recent_blockhash {
+            return Err(Error::validation("The recent blockhash does not match the message's; the file was modified"))
+        }
+        Ok(message)
+    }
+
+    /// Add `signer`'s signature, replacing any earlier one.
+    pub fn sign(&mut self, signer: &dyn Signer) -> Result<(), Error> {
+        let message = self.message()?;
+        let pubkey = signer.pubkey().to_string();
+        let expected = self.signers.iter().map(|slot| slot.pubkey.as_str()).collect::<Vec<_>>().join(", ");
+        let slot = self.signers.iter_mut()
+            .find(|slot| slot.pubkey == pubkey)
+            .ok_or_else(|| Error::keypair(format!("{pubkey} is not a required signer of this transaction, which needs {expected}")))?;
+        let signature = signer.try_sign_message(&message.serialize())
+            .map_err(|e| Error::keypair(format!("Unable to sign with {pubkey}")).with_source(e))?;
+        slot.signature = Some(signature.to_string());
+        Ok(())
+    }
+
+    /// Signers that have not signed yet.
+    pub fn missing_signers(&self) -> Vec<&str> {
+        self.signers.iter().filter(|slot| slot.signature.is_none()).map(|slot| slot.pubkey.as_str()).collect()
+    }
+
+    /// The signed transaction, failing if a signature is missing or does not match the message.
+    pub fn transaction(&self) -> Result<Transaction, Error> {
+        let message = self.message()?;
+        let missing = self.missing_signers();
+        if !missing.is_empty() {
+            return Err(Error::validation(format!("The transaction is missing signatures from {}. Sign it with `rhizo-cli sign`.", missing.join(", "))))
+        }
+        let signatures = self.signers.iter()
+            .map(|slot| Signature::from_str(slot.signature.as_deref().unwrap_or_default())
+                .map_err(|e| Error::validation(format!("The signature of {} is malformed", slot.pubkey)).with_source(e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let transaction = Transaction { signatures, message };
+        let verified = transaction.verify_with_results();
+        if let Some(index) = verified.iter().position(|valid| !valid) {
+            return Err(Error::validation(format!("The signature of {} does not match the message", self.signers[index].pubkey)))
+        }
+        Ok(transaction)
+    }
+
+    /// Read an exported transaction from `path`, or stdin for `-`.
+    pub fn read(path: &Path) -> Result<ExportedTransaction, Error> {
+        let contents = match path.to_str() {
+            Some("-") => std::io::read_to_string(std::io::stdin())
+                .map_err(|e| Error::io("Unable to read the transaction from stdin").with_source(e))?,
+            _ => std::fs::read_to_string(path)
+                .map_err(|e| Error::io(format!("Unable to read the transaction at {}", path.display())).with_source(e))?,
+        };
+        serde_json::from_str(&contents)
+            .map_err(|e| Error::validation(format!("{} is not an exported rhizo transaction", path.display())).with_source(e))
+    }
+
+    /// Write the transaction as JSON to `path`, or stdout for `-`.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self).expect("exported transaction serializes");
+        match path.to_str() {
+            Some("-") => writeln!(std::io::stdout(), "{contents}")
+                .map_err(|e| Error::io("Unable to write the transaction to stdout").with_source(e)),
+            _ => std::fs::write(path, contents + "\n")
+                .map_err(|e| Error::io(format!("Unable to write the transaction to {}", path.display())).with_source(e)),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::RhizoCLIError as Error;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use spinners::{Spinner, Spinners};
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// How command results are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable, tab-aligned text with progress spinners and prompts.
+    #[default]
+    Text,
+    /// A single JSON document per invocation. Spinners and prompts are suppressed, so
+    /// transactions must be approved with `--yes`.
+    Json,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Set the output format for the rest of the process. Only the first call has an effect.
+pub fn init(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> OutputFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+pub fn is_json() -> bool {
+    format() == OutputFormat::Json
+}
+
+/// Print a human readable line. Goes to stderr in JSON mode so stdout only carries the document.
+pub fn note(message: &str) {
+    if is_json() {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+/// Format lamports as SOL followed by the exact amount, e.g. `0.000005 SOL (5000 lamports)`.
+pub fn format_lamports(lamports: u64) -> String {
+    let sol = format!("{}.{:09}", lamports / LAMPORTS_PER_SOL, lamports % LAMPORTS_PER_SOL);
+    format!("{} SOL ({lamports} lamports)", sol.trim_end_matches('0').trim_end_matches('.'))
+}
+
+/// Write `value` to stdout as the command's JSON document.
+pub fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(document) => println!("{document}"),
+        Err(e) => eprintln!("[ERROR] unable to serialize output as JSON: {e}"),
+    }
+}
+
+/// Report a failed command, its cause chain and a remediation hint in the active output format.
+pub fn print_error(command: &str, err: &Error) {
+    if is_json() {
+        print_json(&json!({
+            "error": {
+                "command": command,
+                "kind": err.kind().name(),
+                "message": err.message(),
+                "causes": err.causes(),
+                "hint": err.hint(),
+                "exit_code": err.kind().exit_code(),
+            }
+        }));
+    } else {
+        eprintln!("[ERROR] {command} failed due to error: {err}");
+        for cause in err.causes() {
+            eprintln!("  caused by: {cause}");
+        }
+        if let Some(hint) = err.hint() {
+            eprintln!("  hint: {hint}");
+        }
+    }
+}
+
+/// Progress spinner that is only drawn in text mode with a terminal on stdout.
+pub struct Progress {
+    spinner: Option<Spinner>,
+}
+
+impl Progress {
+    pub fn start(message: &str) -> Progress {
+        let enabled = !is_json() && std::io::stdout().is_terminal();
+        let spinner = enabled.then(|| Spinner::new(Spinners::Dots8Bit, message.to_string()));
+        Progress { spinner }
+    }
+
+    /// Stop the spinner and mark the step as done.
+    pub fn finish(mut self) {
+        if let Some(mut spinner) = self.spinner.take() {
+            spinner.stop_with_symbol("🗸");
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        // A step that failed part way should not leave the cursor on the spinner line.
+        if let Some(mut spinner) = self.spinner.take() {
+            spinner.stop_with_newline();
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use crate::error::{RhizoCLIError as Error, TomlError};
+use crate::retry::{self, RetryPolicy};
+use crate::{tls, utils};
+use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Address of the rhizo program used when no profile overrides it.
+pub const DEFAULT_PROGRAM_ID: &str = "Ep1SV45cqumZmogwWFy6pVNvMpRerMZUUhSJTbTh2e58";
+/// Ingest endpoint used when no profile sets `ingest_urls` or `ingest_url`.
+pub const DEFAULT_INGEST_URL: &str = "http://euro.rhizo.dev/ingest";
+/// Largest gzipped upload the ingest service accepts, in bytes, when no profile overrides it.
+pub const DEFAULT_MAX_MODULE_SIZE: u64 = 2 * 1024 * 1024;
+/// Profile used when neither `--profile` nor `active_profile` names one. It has no settings of
+/// its own, so the RPC URL, keypair and commitment come from the Solana CLI config.
+pub const DEFAULT_PROFILE: &str = "default";
+/// File name of the transaction journal, kept in the config file's directory.
+pub const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+
+/// Keys accepted by `config set`.
+pub const PROFILE_KEYS: &[&str] = &["rpc_url", "ws_url", "program_id", "ingest_url", "ingest_urls", "keypair", "commitment", "max_module_size", "attempts", "timeout_secs", "ca_file"];
+
+/// Contents of ~/.config/rhizo/config.toml.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RhizoConfig {
+    pub active_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// Settings for one network. Unset fields fall back to the built-in profile of the same name,
+/// then to the Solana CLI config and rhizo defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub rpc_url: Option<String>,
+    pub ws_url: Option<String>,
+    pub program_id: Option<String>,
+    pub ingest_url: Option<String>,
+    /// Ingest endpoints deploys upload to, one per region. Takes precedence over `ingest_url`.
+    pub ingest_urls: Option<Vec<String>>,
+    pub keypair: Option<String>,
+    pub commitment: Option<String>,
+    /// Largest gzipped module upload the profile's ingest service accepts, in bytes.
+    pub max_module_size: Option<u64>,
+    /// Attempts made for each RPC or ingest call, including the first.
+    pub attempts: Option<u32>,
+    /// Timeout of a single RPC or ingest request, in seconds.
+    pub timeout_secs: Option<u64>,
+    /// PEM bundle of CA certificates trusted for HTTPS RPC and ingest endpoints in addition to the
+    /// bundled web PKI roots, e.g. for internal mirrors.
+    pub ca_file: Option<String>,
+    /// SPKI pins (`sha256/<base64>`) keyed by RPC or ingest host name. An HTTPS endpoint on a
+    /// pinned host must present one of its pinned keys.
+    pub spki_pins: Option<BTreeMap<String, Vec<String>>>,
+}
+
+impl ProfileConfig {
+    /// Layer `self` over `base`, keeping every field `self` sets.
+    fn or(self, base: ProfileConfig) -> ProfileConfig {
+        ProfileConfig {
+            rpc_url: self.rpc_url.or(base.rpc_url),
+            ws_url: self.ws_url.or(base.ws_url),
+            program_id: self.program_id.or(base.program_id),
+            ingest_url: self.ingest_url.or(base.ingest_url),
+            ingest_urls: self.ingest_urls.or(base.ingest_urls),
+            keypair: self.keypair.or(base.keypair),
+            commitment: self.commitment.or(base.commitment),
+            max_module_size: self.max_module_size.or(base.max_module_size),
+            attempts: self.attempts.or(base.attempts),
+            timeout_secs: self.timeout_secs.or(base.timeout_secs),
+            ca_file: self.ca_file.or(base.ca_file),
+            spki_pins: self.spki_pins.or(base.spki_pins),
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: String) -> Result<(), Error> {
+        match key {
+            "rpc_url" => self.rpc_url = Some(value),
+            "ws_url" => self.ws_url = Some(value),
+            "program_id" => self.program_id = Some(value),
+            // The two ingest keys replace each other so the one set last takes effect.
+            "ingest_url" => (self.ingest_url, self.ingest_urls) = (Some(value), None),
+            "ingest_urls" => (self.ingest_url, self.ingest_urls) = (None, Some(split_list(&value))),
+            "keypair" => self.keypair = Some(value),
+            "commitment" => self.commitment = Some(value),
+            "max_module_size" => self.max_module_size = Some(value.parse()
+                .map_err(|e| Error::validation(format!("{value:?} is not a valid size in bytes")).with_source(e))?),
+            "attempts" => self.attempts = Some(value.parse()
+                .map_err(|e| Error::validation(format!("{value:?} is not a valid number of attempts")).with_source(e))?),
+            "timeout_secs" => self.timeout_secs = Some(value.parse()
+                .map_err(|e| Error::validation(format!("{value:?} is not a valid number of seconds")).with_source(e))?),
+            "ca_file" => self.ca_file = Some(value),
+            other => return Err(Error::validation(format!("Unknown profile key {other:?}, expected one of {}", PROFILE_KEYS.join(", ")))),
+        }
+        Ok(())
+    }
+}
+
+/// Values given on the command line or through RHIZO_* environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileOverrides {
+    pub config_path: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub rpc_url: Option<String>,
+    pub keypair: Option<String>,
+    pub program_id: Option<String>,
+    pub attempts: Option<u32>,
+    pub timeout_secs: Option<u64>,
+    pub ca_file: Option<String>,
+}
+
+/// Fully resolved settings for the network a command runs against.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub program_id: Pubkey,
+    /// Ingest endpoints deploys upload to, never empty.
+    pub ingest_urls: Vec<String>,
+    pub keypair_path: PathBuf,
+    pub commitment: CommitmentConfig,
+    pub max_module_size: u64,
+    pub retry: RetryPolicy,
+    pub ca_file: Option<PathBuf>,
+    /// Normalized SPKI pins keyed by host name.
+    pub spki_pins: BTreeMap<String, Vec<String>>,
+    /// Local record of sent transactions, next to the config file.
+    pub journal_path: PathBuf,
+}
+
+impl Profile {
+    /// Solana Explorer page of a transaction on this profile's cluster.
+    pub fn explorer_url(&self, signature: &str) -> String {
+        let host = url::Url::parse(&self.rpc_url).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default();
+        let cluster = match host {
+            host if host.contains("devnet") => "?cluster=devnet".to_string(),
+            host if host.contains("testnet") => "?cluster=testnet".to_string(),
+            host if host.contains("mainnet") => String::new(),
+            _ => format!("?cluster=custom&customUrl={}", url::form_urlencoded::byte_serialize(self.rpc_url.as_bytes()).collect::<String>()),
+        };
+        format!("https://explorer.solana.com/tx/{signature}{cluster}")
+    }
+}
+
+fn builtin(name: &str) -> ProfileConfig {
+    let (rpc_url, ws_url) = match name {
+        "localnet" => ("http://127.0.0.1:8899", "ws://127.0.0.1:8900"),
+        "devnet" => ("https://api.devnet.solana.com", "wss://api.devnet.solana.com"),
+        "mainnet-beta" => ("https://api.mainnet-beta.solana.com", "wss://api.mainnet-beta.solana.com"),
+        _ => return ProfileConfig::default(),
+    };
+    ProfileConfig { rpc_url: Some(rpc_url.to_string()), ws_url: Some(ws_url.to_string()), ..ProfileConfig::default() }
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    matches!(name, "localnet" | "devnet" | "mainnet-beta" | DEFAULT_PROFILE)
+}
+
+pub fn default_config_path() -> Result<PathBuf, Error> {
+    if let Some(mut path) = home::home_dir() {
+        path.push(".config/rhizo/config.toml");
+        return Ok(path)
+    }
+    Err(Error::config("Unable to find home dir"))
+}
+
+pub fn config_path(overrides: &ProfileOverrides) -> Result<PathBuf, Error> {
+    match &overrides.config_path {
+        Some(path) => Ok(path.clone()),
+        None => default_config_path(),
+    }
+}
+
+/// Read the rhizo config file. A missing file is treated as empty.
+pub fn read_config(path: &Path) -> Result<RhizoConfig, Error> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(RhizoConfig::default()),
+        Err(e) => return Err(Error::config(format!("Unable to read the rhizo config at {}", path.display())).with_source(e)),
+    };
+    toml::from_str(&contents)
+        .map_err(|e| Error::config(format!("Unable to parse the rhizo config at {}", path.display())).with_source(TomlError::new(&contents, &e)))
+}
+
+pub fn write_config(path: &Path, config: &RhizoConfig) -> Result<(), Error> {
+    let contents = toml::to_string_pretty(config)
+        .map_err(|e| Error::config("Unable to serialize the rhizo config").with_source(e))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| Error::io(format!("Unable to create {}", parent.display())).with_source(e))?;
+    }
+    std::fs::write(path, contents)
+        .map_err(|e| Error::io(format!("Unable to write the rhizo config at {}", path.display())).with_source(e))
+}
+
+/// Name of the profile a command runs against.
+pub fn active_name(overrides: &ProfileOverrides, config: &RhizoConfig) -> String {
+    overrides.profile.clone()
+        .or_else(|| config.active_profile.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Resolve the active profile. Precedence is flags and RHIZO_* variables, then the profile in
+/// the rhizo config file, then the built-in profile of the same name, then the Solana CLI config.
+pub fn load(overrides: &ProfileOverrides) -> Result<Profile, Error> {
+    let path = config_path(overrides)?;
+    let mut config = read_config(&path)?;
+    let name = active_name(overrides, &config);
+
+    let stored = config.profiles.remove(&name);
+    if stored.is_none() && !is_builtin(&name) {
+        return Err(Error::config(format!("Profile {name:?} is not defined in {}", path.display())))
+    }
+    let flags = ProfileConfig {
+        rpc_url: overrides.rpc_url.clone(),
+        program_id: overrides.program_id.clone(),
+        keypair: overrides.keypair.clone(),
+        attempts: overrides.attempts,
+        timeout_secs: overrides.timeout_secs,
+        ca_file: overrides.ca_file.clone(),
+        ..ProfileConfig::default()
+    };
+    let settings = flags.or(stored.unwrap_or_default()).or(builtin(&name));
+
+    let needs_solana_config = settings.rpc_url.is_none() || settings.keypair.is_none() || settings.commitment.is_none();
+    let solana_config = if needs_solana_config { utils::get_config().ok().flatten() } else { None };
+    let from_solana = |key: &str| solana_config.as_ref().and_then(|c| c[key].as_str().map(|s| s.to_string()));
+
+    let rpc_url = settings.rpc_url.clone()
+        .or_else(|| from_solana("json_rpc_url"))
+        .ok_or_else(|| Error::config(format!("No RPC URL configured for profile {name:?}. Pass --url or run `rhizo-cli config set rpc_url <url>`.")))?;
+    let keypair = settings.keypair.clone()
+        .or_else(|| from_solana("keypair_path"))
+        .ok_or_else(|| Error::keypair(format!("No keypair configured for profile {name:?}")))?;
+    let commitment = settings.commitment.clone()
+        .or_else(|| from_solana("commitment"))
+        .unwrap_or_else(|| "confirmed".to_string());
+    let program_id = settings.program_id.clone().unwrap_or_else(|| DEFAULT_PROGRAM_ID.to_string());
+    let ingest_urls = settings.ingest_urls.filter(|urls| !urls.is_empty())
+        .or_else(|| settings.ingest_url.map(|url| vec![url]))
+        .unwrap_or_else(|| vec![DEFAULT_INGEST_URL.to_string()]);
+
+    let mut spki_pins = BTreeMap::new();
+    for (host, pins) in settings.spki_pins.unwrap_or_default() {
+        let pins = pins.iter().map(|pin| tls::parse_pin(pin)).collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::config(format!("Invalid spki_pins for {host:?} in profile {name:?}")).with_source(e))?;
+        spki_pins.insert(host, pins);
+    }
+    // Pins can only be checked over TLS, so a pinned host reached over plain HTTP is a mistake.
+    for endpoint in ingest_urls.iter().chain([&rpc_url]) {
+        let Ok(url) = url::Url::parse(endpoint) else { continue };
+        if url.scheme() == "http" && url.host_str().is_some_and(|host| spki_pins.contains_key(host)) {
+            return Err(Error::config(format!("Profile {name:?} pins keys for {} but reaches it over plain HTTP at {endpoint}. Use https://.", url.host_str().unwrap_or_default())))
+        }
+    }
+
+    Ok(Profile {
+        ws_url: settings.ws_url.clone().unwrap_or_else(|| websocket_url(&rpc_url)),
+        rpc_url,
+        program_id: Pubkey::from_str(&program_id)
+            .map_err(|e| Error::config(format!("Program id {program_id:?} of profile {name:?} is not a valid pubkey")).with_source(e))?,
+        ingest_urls,
+        keypair_path: expand_home(&keypair),
+        commitment: parse_commitment(&commitment)?,
+        max_module_size: settings.max_module_size.unwrap_or(DEFAULT_MAX_MODULE_SIZE),
+        retry: RetryPolicy {
+            attempts: settings.attempts.unwrap_or(retry::DEFAULT_ATTEMPTS).max(1),
+            timeout: Duration::from_secs(settings.timeout_secs.unwrap_or(retry::DEFAULT_TIMEOUT_SECS)),
+            ..RetryPolicy::default()
+        },
+        ca_file: settings.ca_file.as_deref().map(expand_home),
+        spki_pins,
+        journal_path: path.with_file_name(JOURNAL_FILE_NAME),
+        name,
+    })
+}
+
+/// Split a comma separated `config set` value, e.g. `http://a/ingest, http://b/ingest`.
+pub fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+pub fn parse_commitment(commitment: &str) -> Result<CommitmentConfig, Error> {
+    match commitment {
+        "processed" => Ok(CommitmentConfig::processed()),
+        "confirmed" => Ok(CommitmentConfig::confirmed()),
+        "finalized" => Ok(CommitmentConfig::finalized()),
+        other => Err(Error::config(format!("Unsupported commitment {other:?}, expected processed, confirmed or finalized"))),
+    }
+}
+
+/// Websocket URL the Solana CLI would derive from an RPC URL.
+fn websocket_url(rpc_url: &str) -> String {
+    let Ok(mut url) = url::Url::parse(rpc_url) else { return String::new() };
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    let _ = url.set_scheme(scheme);
+    if let Some(port) = url.port() {
+        // There is no port above 65535, so that one keeps its port.
+        let _ = url.set_port(Some(port.checked_add(1).unwrap_or(port)));
+    }
+    url.to_string()
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::RhizoCLIError as Error;
+use serde::Serialize;
+use solana_client::client_error::ClientError;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+
+/// A custom error code returned by the rhizo program through `InstructionError::Custom`.
+#[derive(Debug, Serialize)]
+pub struct ProgramErrorCode {
+    pub code: u32,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub remediation: &'static str,
+}
+
+/// Every custom error code the rhizo program is known to return. Codes whose meaning the program
+/// source does not confirm are named "Undocumented"; codes not listed decode as "unknown rhizo
+/// error N".
+pub const PROGRAM_ERRORS: &[ProgramErrorCode] = &[
+    ProgramErrorCode {
+        code: 0,
+        name: "RouteLimitReached",
+        description: "Developer has reached the smart-contract's configured route limit.",
+        remediation: "Yank routes you no longer need with `rhizo-cli yank <route>` and retry.",
+    },
+    ProgramErrorCode {
+        code: 1,
+        name: "Undocumented",
+        description: "The rhizo program returns this code, but its meaning has not been confirmed against the program source. Older clients reported it as \"Nice try.\"",
+        remediation: "Report the failing transaction's signature and the output of `rhizo-cli version` to the rhizo maintainers so the code can be documented.",
+    },
+    ProgramErrorCode {
+        code: 3,
+        name: "Unauthorized",
+        description: "Not authorized to mutate bytes.",
+        remediation: "Only the owner of signed on-chain bytes can write them. Allocate them with `rhizo-cli socb alloc` from the keypair you write with.",
+    },
+];
+
+pub fn lookup(code: u32) -> Option<&'static ProgramErrorCode> {
+    PROGRAM_ERRORS.iter().find(|entry| entry.code == code)
+}
+
+/// One-line description of `code`, e.g. "Unauthorized (3): Not authorized to mutate bytes.".
+pub fn describe(code: u32) -> String {
+    match lookup(code) {
+        Some(entry) => format!("{} ({code}): {}", entry.name, entry.description),
+        None => format!("unknown rhizo error {code}"),
+    }
+}
+
+/// Parse a code as printed by the CLI or in program logs, e.g. `3` or `0x3`.
+pub fn parse_code(code: &str) -> Result<u32, Error> {
+    let parsed = match code.strip_prefix("0x").or_else(|| code.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => code.parse::<u32>(),
+    };
+    parsed.map_err(|e| Error::validation(format!("{code:?} is not a valid error code")).with_source(e))
+}
+
+/// Turn a failed `send_and_confirm_transaction` of `message` into an error. Custom codes are
+/// decoded with the registry only when the failing instruction belongs to `program_id`; those of
+/// other programs, e.g. the System program's nonce and transfer instructions, are shown as is.
+pub fn decode(err: ClientError, message: &Message, program_id: &Pubkey) -> Error {
+    match err.get_transaction_error() {
+        Some(TransactionError::InstructionError(index, instruction_error)) => {
+            let program = message.instructions.get(index as usize)
+                .and_then(|instruction| message.account_keys.get(instruction.program_id_index as usize));
+            match (program, instruction_error) {
+                (Some(program), InstructionError::Custom(code)) if program == program_id => {
+                    Error::program(format!("Instruction {index} failed with {}", describe(code)), Some(code)).with_source(err)
+                }
+                (Some(program), other) => {
+                    Error::program(format!("Instruction {index} (program {program}) failed: {other}"), None).with_source(err)
+                }
+                (None, other) => Error::program(format!("Instruction {index} failed: {other}"), None).with_source(err),
+            }
+        }
+        Some(other) => Error::rpc(other.to_string()).with_source(err),
+        None => Error::rpc("Unable to send the transaction").with_source(err),
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::RhizoCLIError as Error;
+use crate::tls;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_custom_error::{JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY, JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_sdk::transaction::TransactionError;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+/// Attempts made for each network call when no profile overrides it, including the first.
+pub const DEFAULT_ATTEMPTS: u32 = 4;
+/// Per-request timeout when no profile overrides it.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// How often and how patiently RPC and ingest calls are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first.
+    pub attempts: u32,
+    /// Timeout of a single request.
+    pub timeout: Duration,
+    /// Delay before the first retry. Each further retry doubles it, up to `max_delay`.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: DEFAULT_ATTEMPTS,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry`, starting at 1: exponential backoff with jitter, so
+    /// concurrent clients hitting the same rate limit spread out.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.base_delay
+            .saturating_mul(1u32 << retry.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        backoff / 2 + backoff.mul_f64(rand::random::<f64>() / 2.0)
+    }
+
+    /// Call `f` until it succeeds, fails with an error `retryable` rejects, or attempts run out.
+    pub fn run<T, E: Display>(&self, what: &str, mut f: impl FnMut() -> Result<T, E>, retryable: impl Fn(&E) -> bool) -> Result<T, E> {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(e) if attempt < self.attempts && retryable(&e) => {
+                    let delay = self.delay(attempt);
+                    report(what, &e, delay, attempt, self.attempts);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Async version of [`RetryPolicy::run`].
+    pub async fn run_async<T, E: Display, F: Future<Output = Result<T, E>>>(&self, what: &str, mut f: impl FnMut() -> F, retryable: impl Fn(&E) -> bool) -> Result<T, E> {
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Err(e) if attempt < self.attempts && retryable(&e) => {
+                    let delay = self.delay(attempt);
+                    report(what, &e, delay, attempt, self.attempts);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn report(what: &str, err: &dyn Display, delay: Duration, attempt: u32, attempts: u32) {
+    eprintln!("[WARN] {what} failed ({err}); retrying in {}ms (attempt {}/{attempts})", delay.as_millis(), attempt + 1);
+}
+
+/// Whether an RPC call failed for a reason that may go away on its own: a dropped connection,
+/// a timeout, rate limiting, a 5xx or an unhealthy node. Program and other transaction errors,
+/// and rejected certificates, are never retried.
+pub fn is_retryable_rpc(err: &ClientError) -> bool {
+    if tls::is_failure(err) {
+        return false
+    }
+    match err.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => e.is_timeout() || e.is_connect()
+            || e.status().is_some_and(|status| status.as_u16() == 429 || status.is_server_error()),
+        // The client queries the node version before some calls and reports transport failures
+        // of that query only as text.
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(message)) => message.starts_with("cluster version query failed"),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, data, .. }) => match data {
+            RpcResponseErrorData::NodeUnhealthy { .. } => true,
+            RpcResponseErrorData::SendTransactionPreflightFailure(_) => is_blockhash_expired(err),
+            RpcResponseErrorData::Empty => matches!(
+                *code,
+                JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY | JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE | JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET
+            ),
+        },
+        _ => is_blockhash_expired(err),
+    }
+}
+
+/// Whether a transaction was rejected or dropped because its blockhash expired. Such a transaction
+/// can be re-signed with a fresh blockhash and sent again.
+pub fn is_blockhash_expired(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::RpcError(RpcError::ForUser(message)) => message.starts_with("unable to confirm transaction"),
+        _ => matches!(err.get_transaction_error(), Some(TransactionError::BlockhashNotFound)),
+    }
+}
+
+/// Whether an ingest request failed for a reason that may go away on its own: a failed connection,
+/// a timeout, an IO error, rate limiting or a 5xx. Other failures without a status, such as a
+/// request that cannot be built or a module that does not hash to its CID, are never retried.
+pub fn is_retryable_ingest(err: &Error) -> bool {
+    match err {
+        Error::Ingest { status: None, source: Some(source), .. } => is_transient_transport(source.as_ref()),
+        Error::Ingest { status: Some(status), .. } => *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+fn is_transient_transport(err: &(dyn std::error::Error + 'static)) -> bool {
+    if err.is::<tokio::time::error::Elapsed>() {
+        return true
+    }
+    if err.downcast_ref::<hyper::Error>().is_some_and(|e| e.is_connect() || e.is_timeout()) {
+        return true
+    }
+    // Connections reset or closed mid-request surface as IO errors somewhere in the chain.
+    std::iter::successors(Some(err), |e| e.source()).any(|e| e.is::<std::io::Error>())
+}
@@ -0,0 +1,372 @@
+use crate::error::RhizoCLIError as Error;
+use crate::utils;
+use rhizo_types::{ArgumentType, CollectionType, NestedCollectionType};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A schema file for signed on-chain bytes, e.g.
+///
+/// ```toml
+/// [[fields]]
+/// name = "max_items"
+/// type = "u32"
+///
+/// [[fields]]
+/// name = "limits"
+/// fields = [{ name = "burst", type = "u16" }, { name = "tags", type = "vec<str>" }]
+/// ```
+///
+/// Types are the route argument types; a field with `fields` instead of `type` is a nested struct.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaFile {
+    fields: Vec<FieldFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldFile {
+    name: String,
+    #[serde(rename = "type")]
+    field_type: Option<String>,
+    fields: Option<Vec<FieldFile>>,
+}
+
+/// Borsh layout of a value: a route argument type, or a struct whose fields are laid out in order.
+#[derive(Debug)]
+pub enum Layout {
+    Value(ArgumentType),
+    Struct(Vec<(String, Layout)>),
+}
+
+/// Read and check the schema at `path`.
+pub fn load(path: &Path) -> Result<Layout, Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("Unable to read the schema at {}", path.display())).with_source(e))?;
+    let file: SchemaFile = toml::from_str(&contents)
+        .map_err(|e| Error::config(format!("Unable to parse the schema at {}", path.display())).with_source(e))?;
+    layout(&file.fields, "fields")
+        .map_err(|message| Error::config(format!("Schema at {} is invalid: {message}", path.display())))
+}
+
+fn layout(fields: &[FieldFile], at: &str) -> Result<Layout, String> {
+    if fields.is_empty() {
+        return Err(format!("{at}: a struct needs at least one field"))
+    }
+    let mut seen = HashMap::new();
+    let mut struct_fields = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        let at = format!("{at}[{i}]");
+        if field.name.is_empty() {
+            return Err(format!("{at}.name: must not be empty"))
+        }
+        if let Some(first) = seen.insert(field.name.as_str(), i) {
+            return Err(format!("{at}.name: duplicate field name {:?}, already declared at index {first}", field.name))
+        }
+        let field_layout = match (&field.field_type, &field.fields) {
+            (Some(field_type), None) => Layout::Value(utils::parse_argument_type(field_type.clone()).map_err(|e| format!("{at}.type: {}", e.message()))?),
+            (None, Some(fields)) => layout(fields, &format!("{at}.fields"))?,
+            _ => return Err(format!("{at}: give either type or fields")),
+        };
+        struct_fields.push((field.name.clone(), field_layout));
+    }
+    Ok(Layout::Struct(struct_fields))
+}
+
+/// Encode `value` as Borsh following `layout`.
+pub fn encode(layout: &Layout, value: &Value) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    encode_layout(layout, value, "value", &mut out).map_err(Error::validation)?;
+    Ok(out)
+}
+
+/// Decode Borsh `bytes` into JSON following `layout`. Trailing zero bytes, e.g. the rest of an
+/// allocation, are ignored.
+pub fn decode(layout: &Layout, bytes: &[u8]) -> Result<Value, Error> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let value = decode_layout(layout, &mut reader, "value").map_err(Error::validation)?;
+    let rest = &bytes[reader.offset..];
+    if rest.iter().any(|byte| *byte != 0) {
+        return Err(Error::validation(format!("The content has {} bytes after the value at offset {}; the schema does not describe it", rest.len(), reader.offset)))
+    }
+    Ok(value)
+}
+
+fn encode_layout(layout: &Layout, value: &Value, at: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    match layout {
+        Layout::Value(argument_type) => encode_argument(argument_type, value, at, out),
+        Layout::Struct(fields) => {
+            let object = value.as_object().ok_or_else(|| format!("{at}: expected an object, got {}", kind(value)))?;
+            if let Some(unknown) = object.keys().find(|key| !fields.iter().any(|(name, _)| name == *key)) {
+                return Err(format!("{at}.{unknown}: not a field of the schema"))
+            }
+            for (name, field) in fields {
+                let at = format!("{at}.{name}");
+                let value = object.get(name).ok_or_else(|| format!("{at}: missing"))?;
+                encode_layout(field, value, &at, out)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn encode_argument(argument_type: &ArgumentType, value: &Value, at: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    match argument_type {
+        ArgumentType::Array(element) => {
+            let items = array(value, at)?;
+            out.extend_from_slice(&(items.len() as u32).to_le_bytes());
+            for (i, item) in items.iter().enumerate() {
+                encode_collection(element, item, &format!("{at}[{i}]"), out)?;
+            }
+            Ok(())
+        }
+        ArgumentType::Map(..) => Err(format!("{at}: maps are not supported in schemas")),
+        scalar => encode_scalar(Scalar::try_from(scalar).expect("argument type is a scalar"), value, at, out),
+    }
+}
+
+fn encode_collection(collection_type: &CollectionType, value: &Value, at: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    match collection_type {
+        CollectionType::Array(element) => {
+            let items = array(value, at)?;
+            out.extend_from_slice(&(items.len() as u32).to_le_bytes());
+            for (i, item) in items.iter().enumerate() {
+                encode_scalar(Scalar::from(element), item, &format!("{at}[{i}]"), out)?;
+            }
+            Ok(())
+        }
+        CollectionType::Map(..) => Err(format!("{at}: maps are not supported in schemas")),
+        scalar => encode_scalar(Scalar::try_from(scalar).expect("collection type is a scalar"), value, at, out),
+    }
+}
+
+/// The types a Borsh value bottoms out in.
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    U8, U16, U32, U64, I8, I16, I32, I64, F32, F64, Bool, Str,
+}
+
+impl Scalar {
+    fn name(&self) -> &'static str {
+        match self {
+            Scalar::U8 => "u8",
+            Scalar::U16 => "u16",
+            Scalar::U32 => "u32",
+            Scalar::U64 => "u64",
+            Scalar::I8 => "i8",
+            Scalar::I16 => "i16",
+            Scalar::I32 => "i32",
+            Scalar::I64 => "i64",
+            Scalar::F32 => "f32",
+            Scalar::F64 => "f64",
+            Scalar::Bool => "bool",
+            Scalar::Str => "str",
+        }
+    }
+}
+
+impl From<&NestedCollectionType> for Scalar {
+    fn from(nested: &NestedCollectionType) -> Scalar {
+        match nested {
+            NestedCollectionType::U8 => Scalar::U8,
+            NestedCollectionType::U16 => Scalar::U16,
+            NestedCollectionType::U32 => Scalar::U32,
+            NestedCollectionType::U64 => Scalar::U64,
+            NestedCollectionType::I8 => Scalar::I8,
+            NestedCollectionType::I16 => Scalar::I16,
+            NestedCollectionType::I32 => Scalar::I32,
+            NestedCollectionType::I64 => Scalar::I64,
+            NestedCollectionType::F32 => Scalar::F32,
+            NestedCollectionType::F64 => Scalar::F64,
+            NestedCollectionType::Bool => Scalar::Bool,
+            NestedCollectionType::Str => Scalar::Str,
+        }
+    }
+}
+
+impl TryFrom<&ArgumentType> for Scalar {
+    type Error = ();
+
+    fn try_from(argument_type: &ArgumentType) -> Result<Scalar, ()> {
+        Ok(match argument_type {
+            ArgumentType::U8 => Scalar::U8,
+            ArgumentType::U16 => Scalar::U16,
+            ArgumentType::U32 => Scalar::U32,
+            ArgumentType::U64 => Scalar::U64,
+            ArgumentType::I8 => Scalar::I8,
+            ArgumentType::I16 => Scalar::I16,
+            ArgumentType::I32 => Scalar::I32,
+            ArgumentType::I64 => Scalar::I64,
+            ArgumentType::F32 => Scalar::F32,
+            ArgumentType::F64 => Scalar::F64,
+            ArgumentType::Bool => Scalar::Bool,
+            ArgumentType::Str => Scalar::Str,
+            ArgumentType::Array(_) | ArgumentType::Map(..) => return Err(()),
+        })
+    }
+}
+
+impl TryFrom<&CollectionType> for Scalar {
+    type Error = ();
+
+    fn try_from(collection: &CollectionType) -> Result<Scalar, ()> {
+        Ok(match collection {
+            CollectionType::U8 => Scalar::U8,
+            CollectionType::U16 => Scalar::U16,
+            CollectionType::U32 => Scalar::U32,
+            CollectionType::U64 => Scalar::U64,
+            CollectionType::I8 => Scalar::I8,
+            CollectionType::I16 => Scalar::I16,
+            CollectionType::I32 => Scalar::I32,
+            CollectionType::I64 => Scalar::I64,
+            CollectionType::F32 => Scalar::F32,
+            CollectionType::F64 => Scalar::F64,
+            CollectionType::Bool => Scalar::Bool,
+            CollectionType::Str => Scalar::Str,
+            CollectionType::Array(_) | CollectionType::Map(..) => return Err(()),
+        })
+    }
+}
+
+fn encode_scalar(scalar: Scalar, value: &Value, at: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    let mismatch = || format!("{at}: expected {}, got {}", scalar.name(), kind(value));
+    let out_of_range = || format!("{at}: {value} is out of range for {}", scalar.name());
+    let unsigned = || value.as_u64().ok_or_else(|| if value.is_number() { out_of_range() } else { mismatch() });
+    let signed = || value.as_i64().ok_or_else(|| if value.is_number() { out_of_range() } else { mismatch() });
+    match scalar {
+        Scalar::U8 => out.push(u8::try_from(unsigned()?).map_err(|_| out_of_range())?),
+        Scalar::U16 => out.extend_from_slice(&u16::try_from(unsigned()?).map_err(|_| out_of_range())?.to_le_bytes()),
+        Scalar::U32 => out.extend_from_slice(&u32::try_from(unsigned()?).map_err(|_| out_of_range())?.to_le_bytes()),
+        Scalar::U64 => out.extend_from_slice(&unsigned()?.to_le_bytes()),
+        Scalar::I8 => out.extend_from_slice(&i8::try_from(signed()?).map_err(|_| out_of_range())?.to_le_bytes()),
+        Scalar::I16 => out.extend_from_slice(&i16::try_from(signed()?).map_err(|_| out_of_range())?.to_le_bytes()),
+        Scalar::I32 => out.extend_from_slice(&i32::try_from(signed()?).map_err(|_| out_of_range())?.to_le_bytes()),
+        Scalar::I64 => out.extend_from_slice(&signed()?.to_le_bytes()),
+        Scalar::F32 => {
+            let float = value.as_f64().ok_or_else(mismatch)?;
+            if float.abs() > f32::MAX as f64 {
+                return Err(out_of_range())
+            }
+            out.extend_from_slice(&(float as f32).to_le_bytes());
+        }
+        Scalar::F64 => out.extend_from_slice(&value.as_f64().ok_or_else(mismatch)?.to_le_bytes()),
+        Scalar::Bool => out.push(u8::from(value.as_bool().ok_or_else(mismatch)?)),
+        Scalar::Str => {
+            let string = value.as_str().ok_or_else(mismatch)?;
+            out.extend_from_slice(&(string.len() as u32).to_le_bytes());
+            out.extend_from_slice(string.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+fn array<'a>(value: &'a Value, at: &str) -> Result<&'a Vec<Value>, String> {
+    value.as_array().ok_or_else(|| format!("{at}: expected an array, got {}", kind(value)))
+}
+
+/// JSON type of `value` for error messages.
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize, at: &str) -> Result<&[u8], String> {
+        let end = self.offset.checked_add(len).filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("{at}: the content ends at byte {} but {len} more are needed", self.bytes.len()))?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self, at: &str) -> Result<[u8; N], String> {
+        Ok(self.take(N, at)?.try_into().expect("took N bytes"))
+    }
+
+    /// A length prefix, checked against the bytes left so a corrupt one cannot allocate wildly.
+    fn len(&mut self, at: &str) -> Result<usize, String> {
+        let len = u32::from_le_bytes(self.array(at)?) as usize;
+        if len > self.bytes.len() - self.offset {
+            return Err(format!("{at}: length {len} at byte {} is longer than the rest of the content", self.offset - 4))
+        }
+        Ok(len)
+    }
+}
+
+fn decode_layout(layout: &Layout, reader: &mut Reader, at: &str) -> Result<Value, String> {
+    match layout {
+        Layout::Value(argument_type) => decode_argument(argument_type, reader, at),
+        Layout::Struct(fields) => {
+            let mut object = Map::new();
+            for (name, field) in fields {
+                object.insert(name.clone(), decode_layout(field, reader, &format!("{at}.{name}"))?);
+            }
+            Ok(Value::Object(object))
+        }
+    }
+}
+
+fn decode_argument(argument_type: &ArgumentType, reader: &mut Reader, at: &str) -> Result<Value, String> {
+    match argument_type {
+        ArgumentType::Array(element) => {
+            let len = reader.len(at)?;
+            (0..len).map(|i| decode_collection(element, reader, &format!("{at}[{i}]"))).collect::<Result<_, _>>().map(Value::Array)
+        }
+        ArgumentType::Map(..) => Err(format!("{at}: maps are not supported in schemas")),
+        scalar => decode_scalar(Scalar::try_from(scalar).expect("argument type is a scalar"), reader, at),
+    }
+}
+
+fn decode_collection(collection_type: &CollectionType, reader: &mut Reader, at: &str) -> Result<Value, String> {
+    match collection_type {
+        CollectionType::Array(element) => {
+            let len = reader.len(at)?;
+            (0..len).map(|i| decode_scalar(Scalar::from(element), reader, &format!("{at}[{i}]"))).collect::<Result<_, _>>().map(Value::Array)
+        }
+        CollectionType::Map(..) => Err(format!("{at}: maps are not supported in schemas")),
+        scalar => decode_scalar(Scalar::try_from(scalar).expect("collection type is a scalar"), reader, at),
+    }
+}
+
+fn decode_scalar(scalar: Scalar, reader: &mut Reader, at: &str) -> Result<Value, String> {
+    Ok(match scalar {
+        Scalar::U8 => Value::from(reader.array::<1>(at)?[0]),
+        Scalar::U16 => Value::from(u16::from_le_bytes(reader.array(at)?)),
+        Scalar::U32 => Value::from(u32::from_le_bytes(reader.array(at)?)),
+        Scalar::U64 => Value::from(u64::from_le_bytes(reader.array(at)?)),
+        Scalar::I8 => Value::from(i8::from_le_bytes(reader.array(at)?)),
+        Scalar::I16 => Value::from(i16::from_le_bytes(reader.array(at)?)),
+        Scalar::I32 => Value::from(i32::from_le_bytes(reader.array(at)?)),
+        Scalar::I64 => Value::from(i64::from_le_bytes(reader.array(at)?)),
+        Scalar::F32 => float(f32::from_le_bytes(reader.array(at)?) as f64, at)?,
+        Scalar::F64 => float(f64::from_le_bytes(reader.array(at)?), at)?,
+        Scalar::Bool => match reader.array::<1>(at)?[0] {
+            0 => Value::Bool(false),
+            1 => Value::Bool(true),
+            other => return Err(format!("{at}: {other} is not a bool, which is stored as 0 or 1")),
+        },
+        Scalar::Str => {
+            let len = reader.len(at)?;
+            let bytes = reader.take(len, at)?;
+            let string = std::str::from_utf8(bytes).map_err(|e| format!("{at}: the string is not valid UTF-8 ({e})"))?;
+            Value::String(string.to_string())
+        }
+    })
+}
+
+fn float(float: f64, at: &str) -> Result<Value, String> {
+    Number::from_f64(float).map(Value::Number).ok_or_else(|| format!("{at}: {float} cannot be represented in JSON"))
+}
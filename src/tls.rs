@@ -0,0 +1,154 @@
+use base64::Engine;
+use crate::error::RhizoCLIError as Error;
+use crate::profile::Profile;
+use hyper::client::HttpConnector;
+use hyper_rustls::HttpsConnector;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::RpcError;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Prefix of an SPKI pin: `sha256/` followed by the base64 SHA-256 of the certificate's DER
+/// SubjectPublicKeyInfo, the format curl's `--pinnedpubkey` takes.
+pub const PIN_PREFIX: &str = "sha256/";
+
+/// End of the error a certificate whose key is not pinned for its host fails with.
+const PIN_MISMATCH: &str = "which is not one of the keys pinned for it";
+
+/// TLS settings for every HTTPS endpoint the CLI talks to, RPC and ingest alike. Servers must
+/// present a certificate chaining to the bundled web PKI roots or the profile's CA file and, if the
+/// profile pins keys for the host, one of those keys.
+pub fn client_config(profile: &Profile) -> Result<ClientConfig, Error> {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(anchor.subject, anchor.spki, anchor.name_constraints)
+    }));
+    if let Some(ca_file) = &profile.ca_file {
+        add_ca_file(&mut roots, ca_file)?;
+    }
+
+    let verifier = PinningVerifier { inner: WebPkiVerifier::new(roots, None), pins: profile.spki_pins.clone() };
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+/// Connector for ingest requests, using [`client_config`] for `https://` endpoints. `http://`
+/// endpoints connect without TLS.
+pub fn connector(profile: &Profile) -> Result<HttpsConnector<HttpConnector>, Error> {
+    Ok(hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(client_config(profile)?)
+        .https_or_http()
+        .enable_http1()
+        .build())
+}
+
+fn add_ca_file(roots: &mut RootCertStore, path: &Path) -> Result<(), Error> {
+    let contents = std::fs::read(path)
+        .map_err(|e| Error::config(format!("Unable to read the CA file at {}", path.display())).with_source(e))?;
+    let certs = rustls_pemfile::certs(&mut contents.as_slice())
+        .map_err(|e| Error::config(format!("Unable to parse the CA file at {}", path.display())).with_source(e))?;
+    if certs.is_empty() {
+        return Err(Error::config(format!("The CA file at {} contains no PEM certificates", path.display())))
+    }
+    for cert in certs {
+        roots.add(&Certificate(cert))
+            .map_err(|e| Error::config(format!("The CA file at {} contains an invalid certificate", path.display())).with_source(e))?;
+    }
+    Ok(())
+}
+
+/// SPKI pin of a DER certificate, e.g. `sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=`.
+/// `None` if the certificate cannot be parsed.
+pub fn spki_pin(der: &[u8]) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    let digest = Sha256::digest(cert.tbs_certificate.subject_pki.raw);
+    Some(format!("{PIN_PREFIX}{}", base64::engine::general_purpose::STANDARD.encode(digest)))
+}
+
+/// Normalize a configured pin to `sha256/<base64>`, accepting it with or without the prefix.
+pub fn parse_pin(pin: &str) -> Result<String, Error> {
+    let encoded = pin.strip_prefix(PIN_PREFIX).unwrap_or(pin);
+    match base64::engine::general_purpose::STANDARD.decode(encoded) {
+        Ok(digest) if digest.len() == 32 => Ok(format!("{PIN_PREFIX}{encoded}")),
+        _ => Err(Error::config(format!("SPKI pin {pin:?} is not a base64 SHA-256 digest like {PIN_PREFIX}<44 base64 characters>"))),
+    }
+}
+
+/// Web PKI verification followed by a check of the end-entity key against the host's pins.
+struct PinningVerifier {
+    inner: WebPkiVerifier,
+    /// Pins keyed by host name.
+    pins: BTreeMap<String, Vec<String>>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_string(),
+            ServerName::IpAddress(ip) => ip.to_string(),
+            _ => return Ok(verified),
+        };
+        let Some(pins) = self.pins.get(&host) else { return Ok(verified) };
+
+        let pin = spki_pin(&end_entity.0)
+            .ok_or(rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        if pins.contains(&pin) {
+            return Ok(verified)
+        }
+        Err(rustls::Error::General(format!("the certificate of {host} has public key {pin}, {PIN_MISMATCH}")))
+    }
+}
+
+/// Describe a TLS failure found in an error's cause chain, or `None` if it is not one.
+pub fn describe_failure(err: &(dyn std::error::Error + 'static), host: &str) -> Option<String> {
+    Some(match find_failure(err)? {
+        rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer) => format!("the certificate of {host} is not signed by a trusted CA"),
+        rustls::Error::InvalidCertificate(CertificateError::NotValidForName) => format!("the certificate presented is not valid for {host}"),
+        rustls::Error::InvalidCertificate(CertificateError::Expired) => format!("the certificate of {host} has expired"),
+        rustls::Error::InvalidCertificate(CertificateError::NotValidYet) => format!("the certificate of {host} is not valid yet"),
+        rustls::Error::InvalidCertificate(CertificateError::Revoked) => format!("the certificate of {host} has been revoked"),
+        rustls::Error::General(message) => message.clone(),
+        other => format!("{other}"),
+    })
+}
+
+/// Whether an error's cause chain contains a TLS failure. Those do not go away on retry.
+pub fn is_failure(err: &(dyn std::error::Error + 'static)) -> bool {
+    // The RPC client reports failures of its node version query only as text.
+    let reported = std::iter::successors(Some(err), |e| e.source()).any(|e| match e.downcast_ref::<ClientError>().map(ClientError::kind) {
+        Some(ClientErrorKind::RpcError(RpcError::RpcRequestError(message))) => message.contains("invalid peer certificate") || message.contains(PIN_MISMATCH),
+        _ => false,
+    });
+    reported || find_failure(err).is_some()
+}
+
+fn find_failure<'a>(err: &'a (dyn std::error::Error + 'static)) -> Option<&'a rustls::Error> {
+    let mut next = Some(err);
+    while let Some(err) = next {
+        if let Some(tls) = err.downcast_ref::<rustls::Error>() {
+            return Some(tls)
+        }
+        // rustls errors reach hyper wrapped in io::Errors, whose source() skips the wrapped error.
+        next = match err.downcast_ref::<std::io::Error>().and_then(|e| e.get_ref()) {
+            Some(inner) => Some(inner),
+            None => err.source(),
+        };
+    }
+    None
+}
@@ -1,6 +1,14 @@
+use crate::utils;
 use serde::Deserialize;
+use solana_sdk::pubkey::MAX_SEED_LEN;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Prefix of the PDA seed a route is stored under.
+pub const ROUTE_SEED_PREFIX: &str = "route-";
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub encodings: Vec<String>,
     pub arguments: Vec<Argument>,
@@ -10,7 +18,69 @@ pub struct Config {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Argument {
     pub name: String,
     pub argument_type: String,
 }
+
+/// A rule a route config breaks, with the path of the offending field, e.g. `arguments[1].name`.
+#[derive(Debug)]
+pub struct Violation {
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl Config {
+    /// Every rule the config breaks. `deploy` relies on an empty result to build the on-chain
+    /// route data without further checks.
+    pub fn violations(&self) -> Vec<Violation> {
+        let mut violations = vec![];
+        let mut violation = |field: String, message: String| violations.push(Violation { field, message });
+
+        let seed_len = ROUTE_SEED_PREFIX.len() + self.route.len();
+        if self.route.is_empty() {
+            violation("route".to_string(), "must not be empty".to_string());
+        } else if seed_len > MAX_SEED_LEN {
+            violation("route".to_string(), format!(
+                "the account seed \"{ROUTE_SEED_PREFIX}{}\" is {seed_len} bytes, Solana allows at most {MAX_SEED_LEN} (route names can be up to {} bytes)",
+                self.route, MAX_SEED_LEN - ROUTE_SEED_PREFIX.len()
+            ));
+        }
+
+        if self.encodings.is_empty() {
+            violation("encodings".to_string(), "at least one encoding is required".to_string());
+        }
+        let mut seen_encodings = HashMap::new();
+        for (i, encoding) in self.encodings.iter().enumerate() {
+            if let Err(e) = utils::parse_encoding(encoding) {
+                violation(format!("encodings[{i}]"), e.message().to_string());
+            } else if let Some(first) = seen_encodings.insert(encoding.to_lowercase(), i) {
+                violation(format!("encodings[{i}]"), format!("duplicate encoding {encoding:?}, already listed at encodings[{first}]"));
+            }
+        }
+
+        let mut seen_arguments = HashMap::new();
+        for (i, argument) in self.arguments.iter().enumerate() {
+            if argument.name.is_empty() {
+                violation(format!("arguments[{i}].name"), "must not be empty".to_string());
+            } else if let Some(first) = seen_arguments.insert(argument.name.as_str(), i) {
+                violation(format!("arguments[{i}].name"), format!("duplicate argument name {:?}, already declared at arguments[{first}]", argument.name));
+            }
+            if let Err(e) = utils::parse_argument_type(argument.argument_type.clone()) {
+                violation(format!("arguments[{i}].argument_type"), e.message().to_string());
+            }
+        }
+
+        if !self.cacheable && self.cache_ttl_ms.is_some() {
+            violation("cache_ttl_ms".to_string(), "is set but cacheable = false, so it would be ignored".to_string());
+        }
+        violations
+    }
+}